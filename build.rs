@@ -0,0 +1,27 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Perceived lightness to PWM level table for `src/common/gamma.rs`.
+const CIE_STEPS: usize = 65;
+const CIE_MAX: f64 = 1023.0;
+
+fn main() {
+    let mut lut = format!("pub const CIE_LUT: [u16; {}] = [\n", CIE_STEPS);
+    for i in 0..CIE_STEPS {
+        // CIE 1976 lightness L* (0..=100) back to relative luminance.
+        let lightness = 100.0 * i as f64 / (CIE_STEPS - 1) as f64;
+        let luminance = if lightness <= 8.0 {
+            lightness / 903.3
+        } else {
+            ((lightness + 16.0) / 116.0).powi(3)
+        };
+        lut.push_str(&format!("    {},\n", (luminance * CIE_MAX).round() as u16));
+    }
+    lut.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("cie_lut.rs"), lut).unwrap();
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,130 @@
+//! Filtering and scaling of noisy 10-bit analog inputs like potentiometers.
+
+use super::fixed::isqrt;
+
+const ADC_MAX: u16 = 1023;
+// Readings this close to either end count as the end, so the output reaches
+// 0 and its maximum despite the noise.
+const END_BAND: u16 = 4;
+// Fractional bits of the moving average.
+const FRACTION: u32 = 6;
+
+/// How the knob position maps to the output.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Curve {
+    Linear,
+    /// Finer at the low end, `x²`.
+    Quadratic,
+    /// Even finer at the low end, `x³`.
+    Cubic,
+    /// Finer at the high end, `√x`.
+    SquareRoot,
+}
+
+/// Optional median of three against single spikes, then an exponential
+/// moving average, then scaling to `0..=max` along a [`Curve`].
+pub struct AnalogFilter {
+    average: u32,
+    primed: bool,
+    smoothing: u8,
+    median: bool,
+    window: [u16; 3],
+    curve: Curve,
+    max: u16,
+}
+
+impl AnalogFilter {
+    /// Averages over about 8 samples, linear, 0..=255.
+    pub fn new() -> Self {
+        AnalogFilter {
+            average: 0,
+            primed: false,
+            smoothing: 3,
+            median: false,
+            window: [0; 3],
+            curve: Curve::Linear,
+            max: 255,
+        }
+    }
+
+    /// The average moves by `1 / 2^smoothing` of the difference each sample,
+    /// 0 turns it off.
+    pub fn with_smoothing(mut self, smoothing: u8) -> Self {
+        self.smoothing = smoothing.min(FRACTION as u8);
+        self
+    }
+
+    pub fn with_median(mut self, median: bool) -> Self {
+        self.median = median;
+        self
+    }
+
+    pub fn with_curve(mut self, curve: Curve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn with_max(mut self, max: u16) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Feeds a raw reading and returns the scaled output.
+    pub fn update(&mut self, raw: u16) -> u16 {
+        let raw = raw.min(ADC_MAX);
+        let sample = if self.median {
+            if !self.primed {
+                self.window = [raw; 3];
+            }
+            self.window = [self.window[1], self.window[2], raw];
+            median(self.window)
+        } else {
+            raw
+        };
+
+        let target = (sample as u32) << FRACTION;
+        if !self.primed {
+            self.average = target;
+            self.primed = true;
+        } else if target > self.average {
+            self.average += (target - self.average) >> self.smoothing;
+        } else {
+            self.average -= (self.average - target) >> self.smoothing;
+        }
+
+        self.output()
+    }
+
+    /// Filtered reading, 0..=1023.
+    pub fn value(&self) -> u16 {
+        ((self.average + (1 << (FRACTION - 1))) >> FRACTION) as u16
+    }
+
+    /// Filtered reading scaled to `0..=max` along the curve.
+    pub fn output(&self) -> u16 {
+        let value = self.value();
+        let span = (ADC_MAX - 2 * END_BAND) as u32;
+        let x = (value.clamp(END_BAND, ADC_MAX - END_BAND) - END_BAND) as u32;
+        let max = self.max as u32;
+
+        let scaled = match self.curve {
+            Curve::Linear => (x * max + span / 2) / span,
+            Curve::Quadratic => (x * x / span * max + span / 2) / span,
+            Curve::Cubic => (x * x / span * x / span * max + span / 2) / span,
+            Curve::SquareRoot => isqrt((x * span) as u64) * max / span,
+        };
+        scaled as u16
+    }
+}
+
+fn median([a, b, c]: [u16; 3]) -> u16 {
+    a.max(b).min(a.min(b).max(c))
+}
+
+/// Adds `extra_bits` of resolution to a 10-bit input by summing `4^extra_bits`
+/// readings and dropping half the added bits. It only works when the
+/// readings are noisy by at least a count, which the AVR ADC normally is.
+pub fn oversample(mut read: impl FnMut() -> u16, extra_bits: u8) -> u32 {
+    let sum: u32 = (0..1u32 << (2 * extra_bits)).map(|_| read() as u32).sum();
+    sum >> extra_bits
+}
@@ -0,0 +1,190 @@
+//! Keyframe animations for the RGB LED.
+//!
+//! Nothing here waits. The program calls [`Animator::update`] with the current
+//! time in ms as often as it likes and shows the returned color, so it can
+//! keep polling buttons in between.
+
+use heapless::Vec;
+
+use super::color::{hsv, Rgb};
+
+pub const MAX_FRAMES: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transition {
+    /// Jump to the color and hold it.
+    Step,
+    /// Go from the previous color to this one at a constant rate.
+    Linear,
+    /// Like `Linear`, but starting and ending slowly.
+    Smooth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Keyframe {
+    pub color: Rgb,
+    /// How long the transition (or for `Step` the hold) takes, in ms.
+    pub duration: u16,
+    pub transition: Transition,
+}
+
+impl Keyframe {
+    pub const fn new(color: Rgb, duration: u16, transition: Transition) -> Self {
+        Keyframe { color, duration, transition }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Repeat {
+    Once,
+    Times(u8),
+    Forever,
+}
+
+enum Program {
+    Idle,
+    Frames { frames: Vec<Keyframe, MAX_FRAMES>, repeat: Repeat },
+    Rainbow { period: u16, value: u8 },
+}
+
+pub struct Animator {
+    program: Program,
+    index: usize,
+    start: u32,
+    from: Rgb,
+    color: Rgb,
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Animator {
+            program: Program::Idle,
+            index: 0,
+            start: 0,
+            from: Rgb::OFF,
+            color: Rgb::OFF,
+        }
+    }
+
+    /// Plays `frames` starting from the color shown now. Frames past
+    /// [`MAX_FRAMES`] are ignored, no frames at all or [`Repeat::Times`]
+    /// zero is a [`Animator::stop`].
+    pub fn play(&mut self, frames: &[Keyframe], repeat: Repeat, now: u32) {
+        if frames.is_empty() || repeat == Repeat::Times(0) {
+            self.stop();
+            return;
+        }
+        let frames = Vec::from_slice(&frames[..frames.len().min(MAX_FRAMES)]).unwrap();
+        let total: u32 = frames.iter().map(|f| f.duration as u32).sum();
+        // Repeating frames that take no time would never let `update` return.
+        let repeat = if total == 0 { Repeat::Once } else { repeat };
+        self.program = Program::Frames { frames, repeat };
+        self.restart(now);
+    }
+
+    pub fn fade(&mut self, to: Rgb, duration: u16, now: u32) {
+        self.play(&[Keyframe::new(to, duration, Transition::Linear)], Repeat::Once, now);
+    }
+
+    /// Slowly brightens and dims `color`, one breath takes `period` ms.
+    pub fn breathe(&mut self, color: Rgb, period: u16, now: u32) {
+        let frames = [
+            Keyframe::new(color, period / 2, Transition::Smooth),
+            Keyframe::new(Rgb::OFF, period / 2, Transition::Smooth),
+        ];
+        self.play(&frames, Repeat::Forever, now);
+    }
+
+    pub fn blink(&mut self, color: Rgb, on: u16, off: u16, times: u8, now: u32) {
+        let frames = [
+            Keyframe::new(color, on, Transition::Step),
+            Keyframe::new(Rgb::OFF, off, Transition::Step),
+        ];
+        self.play(&frames, Repeat::Times(times), now);
+    }
+
+    /// Goes around the color wheel once every `period` ms.
+    pub fn rainbow(&mut self, period: u16, value: u8, now: u32) {
+        self.program = Program::Rainbow { period: period.max(1), value };
+        self.restart(now);
+    }
+
+    /// Stops and keeps the color shown now.
+    pub fn stop(&mut self) {
+        self.program = Program::Idle;
+    }
+
+    pub fn is_running(&self) -> bool {
+        !matches!(self.program, Program::Idle)
+    }
+
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+
+    /// Color to show at `now` (ms, may wrap).
+    pub fn update(&mut self, now: u32) -> Rgb {
+        let elapsed = now.wrapping_sub(self.start);
+        match &mut self.program {
+            Program::Idle => {}
+            Program::Rainbow { period, value } => {
+                let hue = (elapsed % *period as u32 * 360 / *period as u32) as u16;
+                self.color = hsv(hue, 255, *value);
+            }
+            Program::Frames { frames, repeat } => {
+                let mut elapsed = elapsed;
+                loop {
+                    let frame = frames[self.index];
+                    if elapsed < frame.duration as u32 {
+                        self.color = interpolate(self.from, &frame, elapsed);
+                        break;
+                    }
+
+                    elapsed -= frame.duration as u32;
+                    self.start = self.start.wrapping_add(frame.duration as u32);
+                    self.from = frame.color;
+                    self.color = frame.color;
+                    self.index += 1;
+
+                    if self.index == frames.len() {
+                        self.index = 0;
+                        match repeat {
+                            Repeat::Forever => {}
+                            Repeat::Times(n) if *n > 1 => *n -= 1,
+                            _ => {
+                                self.program = Program::Idle;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.color
+    }
+
+    fn restart(&mut self, now: u32) {
+        self.index = 0;
+        self.start = now;
+        self.from = self.color;
+    }
+}
+
+/// Color `elapsed` ms into `frame`, coming from `from`.
+pub fn interpolate(from: Rgb, frame: &Keyframe, elapsed: u32) -> Rgb {
+    if frame.duration == 0 {
+        return frame.color;
+    }
+    let t = (elapsed.min(frame.duration as u32) * 255 / frame.duration as u32) as u8;
+    match frame.transition {
+        Transition::Step => frame.color,
+        Transition::Linear => from.mix(frame.color, t),
+        Transition::Smooth => from.mix(frame.color, smoothstep(t)),
+    }
+}
+
+/// `3t² - 2t³` with `t` and the result scaled to 0..=255.
+pub fn smoothstep(t: u8) -> u8 {
+    let t = t as u32;
+    (t * t * (765 - 2 * t) / 65025) as u8
+}
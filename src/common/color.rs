@@ -0,0 +1,67 @@
+//! Colors for the RGB LED.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const OFF: Rgb = Rgb::new(0, 0, 0);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::new(0, 0, 255);
+    pub const YELLOW: Rgb = Rgb::new(255, 255, 0);
+    pub const CYAN: Rgb = Rgb::new(0, 255, 255);
+    pub const MAGENTA: Rgb = Rgb::new(255, 0, 255);
+    pub const WHITE: Rgb = Rgb::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// `self` blended with `other`, `amount` goes from 0 (all `self`) to 255
+    /// (all `other`).
+    pub fn mix(self, other: Rgb, amount: u8) -> Rgb {
+        let blend = |a: u8, b: u8| {
+            ((a as u16 * (255 - amount) as u16 + b as u16 * amount as u16 + 127) / 255) as u8
+        };
+        Rgb::new(blend(self.r, other.r), blend(self.g, other.g), blend(self.b, other.b))
+    }
+
+    pub fn scale(self, brightness: u8) -> Rgb {
+        Rgb::OFF.mix(self, brightness)
+    }
+}
+
+/// HSV to RGB with integers only.
+///
+/// `hue` is in degrees (wraps at 360), `saturation` and `value` are 0..=255.
+/// The result is within 1 of the floating point conversion, the channels are
+/// exact except for halves, which are rounded up.
+pub fn hsv(hue: u16, saturation: u8, value: u8) -> Rgb {
+    // One sector is 60 degrees, so fractions are in 1/60 and `s * f` in
+    // 1/(255 * 60).
+    const FULL: u32 = 255 * 60;
+
+    let hue = hue % 360;
+    let sector = hue / 60;
+    let f = (hue % 60) as u32;
+    let v = value as u32;
+    let s = saturation as u32;
+
+    let p = ((v * (255 - s) + 127) / 255) as u8;
+    let q = ((v * (FULL - s * f) + FULL / 2) / FULL) as u8;
+    let t = ((v * (FULL - s * (60 - f)) + FULL / 2) / FULL) as u8;
+    let v = value;
+
+    match sector {
+        0 => Rgb::new(v, t, p),
+        1 => Rgb::new(q, v, p),
+        2 => Rgb::new(p, v, t),
+        3 => Rgb::new(p, q, v),
+        4 => Rgb::new(t, p, v),
+        _ => Rgb::new(v, p, q),
+    }
+}
@@ -0,0 +1,96 @@
+//! Fixed-point math, and numbers as text without pulling in `core::fmt`.
+
+/// Longest text [`format`] writes: a sign, ten digits and the point.
+pub const MAX_LEN: usize = 12;
+
+/// Writes `value` divided by 10 to the power of `decimals` (at most 9) with
+/// exactly `decimals` digits after the point, e.g. 215 with one decimal is
+/// "21.5" and -5 is "-0.5".
+pub fn format(value: i32, decimals: u8, buf: &mut [u8; MAX_LEN]) -> &str {
+    let mut rest = value.unsigned_abs();
+    let mut start = MAX_LEN;
+    let mut digits = 0;
+    while rest > 0 || digits <= decimals {
+        if digits == decimals && decimals > 0 {
+            start -= 1;
+            buf[start] = b'.';
+        }
+        start -= 1;
+        buf[start] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+        digits += 1;
+    }
+    if value < 0 {
+        start -= 1;
+        buf[start] = b'-';
+    }
+    // Only ASCII digits, the point and the sign went in.
+    core::str::from_utf8(&buf[start..]).unwrap_or("")
+}
+
+/// Rounds halves away from zero, plain division truncates towards it.
+pub fn divide_rounded(value: i32, divisor: i32) -> i32 {
+    if value < 0 {
+        (value - divisor / 2) / divisor
+    } else {
+        (value + divisor / 2) / divisor
+    }
+}
+
+/// Square root rounded down.
+pub fn isqrt(value: u64) -> u32 {
+    let mut result = 0u64;
+    let mut bit = 1u64 << 62;
+    let mut rest = value;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rest >= result + bit {
+            rest -= result + bit;
+            result = (result >> 1) + bit;
+        } else {
+            result >>= 1;
+        }
+        bit >>= 2;
+    }
+    result as u32
+}
+
+/// Base 2 logarithm of `value` (at least 1) with 16 fraction bits.
+pub fn log2(value: u32) -> i32 {
+    let whole = 31 - value.max(1).leading_zeros();
+    // Mantissa between 1 and 2, its logarithm bit by bit by squaring.
+    let mut mantissa = ((value as u64) << 16) >> whole;
+    let mut fraction = 0;
+    for bit in (0..16).rev() {
+        mantissa = (mantissa * mantissa) >> 16;
+        if mantissa >= 2 << 16 {
+            mantissa >>= 1;
+            fraction |= 1 << bit;
+        }
+    }
+    ((whole as i32) << 16) | fraction
+}
+
+/// 2 to the power of `value` with 16 fraction bits, rounded to a whole
+/// number and saturating at `u32::MAX`.
+pub fn exp2(value: i32) -> u32 {
+    let whole = value >> 16;
+    if whole >= 32 {
+        return u32::MAX;
+    }
+    if whole < -1 {
+        return 0;
+    }
+
+    // e^y for y = fraction * ln 2, from the Taylor series.
+    let y = ((value as u64 & 0xFFFF) * 45426) >> 16;
+    let mut power = 1u64 << 16;
+    for k in (1..=6).rev() {
+        power = (1 << 16) + ((y * power) >> 16) / k;
+    }
+
+    let shifted = if whole >= 16 { power << (whole - 16) } else { (power + (1 << (15 - whole))) >> (16 - whole) };
+    shifted.min(u32::MAX as u64) as u32
+}
@@ -0,0 +1,26 @@
+//! Perceptual brightness correction for PWM LEDs.
+//!
+//! The eye is far more sensitive to changes at the dark end, so a linear duty
+//! cycle looks like it jumps at the bottom and barely moves at the top. The
+//! table follows CIE lightness and is generated by `build.rs`. It only has 65
+//! entries and is interpolated, 256 `u16`s would take a quarter of the RAM.
+
+include!(concat!(env!("OUT_DIR"), "/cie_lut.rs"));
+
+/// Levels returned by [`correct`] go up to this.
+pub const LEVEL_MAX: u16 = 1023;
+
+/// Linear PWM level (0..=[`LEVEL_MAX`]) that looks `lightness` (0..=255)
+/// bright. That is two bits more than the 8-bit timers have, `RgbLed` gets
+/// them back with dithering.
+pub fn correct(lightness: u8) -> u16 {
+    let position = lightness as u32 * (CIE_LUT.len() as u32 - 1);
+    let index = (position / 255) as usize;
+    let fraction = position % 255;
+    if fraction == 0 {
+        return CIE_LUT[index];
+    }
+    let low = CIE_LUT[index] as u32;
+    let high = CIE_LUT[index + 1] as u32;
+    ((low * (255 - fraction) + high * fraction + 127) / 255) as u16
+}
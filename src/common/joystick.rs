@@ -0,0 +1,379 @@
+//! Analog joystick: calibration, normalized axes and direction events.
+//!
+//! Axes are normalized to -1000..=1000, which keeps all the math in `i32` and
+//! is fine enough for a 10-bit ADC. Positive X is right, positive Y is up
+//! (towards higher raw readings).
+
+use super::fixed::isqrt;
+
+pub const FULL_SCALE: i32 = 1000;
+
+pub const CALIBRATION_SIZE: usize = 14;
+
+// A calibration sweep narrower than this is most likely a stick that did not
+// move at all.
+const MIN_RANGE: u16 = 100;
+
+// Direction events: leave the center above ENGAGE, return to it below RELEASE,
+// switch to a neighbouring direction only HYSTERESIS degrees past the border
+// and only after DEBOUNCE samples agree.
+const ENGAGE: u16 = 500;
+const RELEASE: u16 = 350;
+const HYSTERESIS: u16 = 8;
+const DEBOUNCE: u8 = 3;
+
+/// A single ADC input.
+///
+/// embedded-hal 1.0 has no ADC traits, this is the one method we need from
+/// them. `A` is the ADC the channel is read through.
+pub trait AnalogChannel<A> {
+    fn read(&self, adc: &mut A) -> u16;
+}
+
+#[cfg(target_arch = "avr")]
+impl AnalogChannel<arduino_hal::Adc> for arduino_hal::adc::Channel {
+    fn read(&self, adc: &mut arduino_hal::Adc) -> u16 {
+        adc.read_blocking(self)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Axis {
+    pub min: u16,
+    pub center: u16,
+    pub max: u16,
+}
+
+impl Axis {
+    /// Signed deflection, 0 at the center and ±[`FULL_SCALE`] at the ends.
+    pub fn normalize(&self, raw: u16) -> i32 {
+        if raw >= self.center {
+            let span = (self.max - self.center).max(1) as i32;
+            ((raw - self.center) as i32 * FULL_SCALE / span).min(FULL_SCALE)
+        } else {
+            let span = (self.center - self.min).max(1) as i32;
+            -((self.center - raw) as i32 * FULL_SCALE / span).min(FULL_SCALE)
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.min < self.center && self.center < self.max && self.max - self.min >= MIN_RANGE
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Calibration {
+    pub x: Axis,
+    pub y: Axis,
+    /// Radius of the dead zone around the center, in the same units as the
+    /// normalized axes.
+    pub dead_zone: u16,
+}
+
+impl Calibration {
+    /// Ideal stick, used until a real calibration is done.
+    pub const DEFAULT: Calibration = Calibration {
+        x: Axis { min: 0, center: 512, max: 1023 },
+        y: Axis { min: 0, center: 512, max: 1023 },
+        dead_zone: 80,
+    };
+
+    /// Normalized `(x, y)` of a raw reading.
+    ///
+    /// Inside the dead zone this is `(0, 0)`. Outside, the radius is rescaled
+    /// so it starts from zero at the edge of the dead zone instead of jumping.
+    pub fn apply(&self, x_raw: u16, y_raw: u16) -> (i32, i32) {
+        let x = self.x.normalize(x_raw);
+        let y = self.y.normalize(y_raw);
+        let dead_zone = self.dead_zone as i32;
+
+        let radius = isqrt((x * x + y * y) as u64) as i32;
+        if radius <= dead_zone {
+            return (0, 0);
+        }
+
+        let scaled = ((radius - dead_zone) * FULL_SCALE / (FULL_SCALE - dead_zone)).min(FULL_SCALE);
+        (x * scaled / radius, y * scaled / radius)
+    }
+
+    pub fn to_bytes(self) -> [u8; CALIBRATION_SIZE] {
+        let values = [
+            self.x.min,
+            self.x.center,
+            self.x.max,
+            self.y.min,
+            self.y.center,
+            self.y.max,
+            self.dead_zone,
+        ];
+        let mut bytes = [0; CALIBRATION_SIZE];
+        for (i, value) in values.iter().enumerate() {
+            bytes[2 * i..2 * i + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; CALIBRATION_SIZE]) -> Option<Self> {
+        let value = |i: usize| u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]);
+        let calibration = Calibration {
+            x: Axis { min: value(0), center: value(1), max: value(2) },
+            y: Axis { min: value(3), center: value(4), max: value(5) },
+            dead_zone: value(6),
+        };
+        let valid = calibration.x.is_valid()
+            && calibration.y.is_valid()
+            && (calibration.dead_zone as i32) < FULL_SCALE;
+        valid.then_some(calibration)
+    }
+
+    #[cfg(target_arch = "avr")]
+    pub fn load(eep: &mut arduino_hal::eeprom::Eeprom, addr: u16) -> Option<Self> {
+        let mut bytes = [0; CALIBRATION_SIZE];
+        if super::persist::load(eep, addr, &mut bytes) {
+            Self::from_bytes(&bytes)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(target_arch = "avr")]
+    pub fn store(&self, eep: &mut arduino_hal::eeprom::Eeprom, addr: u16) {
+        super::persist::store(eep, addr, &self.to_bytes());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Center,
+    Right,
+    UpRight,
+    Up,
+    UpLeft,
+    Left,
+    DownLeft,
+    Down,
+    DownRight,
+}
+
+impl Direction {
+    const AROUND: [Direction; 8] = [
+        Direction::Right,
+        Direction::UpRight,
+        Direction::Up,
+        Direction::UpLeft,
+        Direction::Left,
+        Direction::DownLeft,
+        Direction::Down,
+        Direction::DownRight,
+    ];
+
+    /// Direction of the 45° sector `angle` (in degrees) falls into.
+    pub fn from_angle(angle: u16) -> Self {
+        Self::AROUND[((angle % 360 + 22) / 45) as usize % 8]
+    }
+
+    /// Angle in degrees, `None` for [`Direction::Center`].
+    pub fn angle(self) -> Option<u16> {
+        Self::AROUND
+            .iter()
+            .position(|&d| d == self)
+            .map(|i| i as u16 * 45)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Center => "Center",
+            Direction::Right => "Right",
+            Direction::UpRight => "UpRight",
+            Direction::Up => "Up",
+            Direction::UpLeft => "UpLeft",
+            Direction::Left => "Left",
+            Direction::DownLeft => "DownLeft",
+            Direction::Down => "Down",
+            Direction::DownRight => "DownRight",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Reading {
+    pub x_raw: u16,
+    pub y_raw: u16,
+    /// Normalized axes with the dead zone applied.
+    pub x: i32,
+    pub y: i32,
+    /// Degrees counter-clockwise from the right, 0..360.
+    pub angle: u16,
+    /// Deflection from the center, 0..=[`FULL_SCALE`].
+    pub magnitude: u16,
+}
+
+pub struct Joystick<X, Y> {
+    x: X,
+    y: Y,
+    calibration: Calibration,
+    direction: Direction,
+    pending: Direction,
+    pending_count: u8,
+}
+
+impl<X, Y> Joystick<X, Y> {
+    pub fn new(x: X, y: Y, calibration: Calibration) -> Self {
+        Joystick {
+            x,
+            y,
+            calibration,
+            direction: Direction::Center,
+            pending: Direction::Center,
+            pending_count: 0,
+        }
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// Debounced direction the stick is held in.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Raw `(x, y)` readings, for calibration.
+    pub fn read_raw<A>(&self, adc: &mut A) -> (u16, u16)
+    where
+        X: AnalogChannel<A>,
+        Y: AnalogChannel<A>,
+    {
+        (self.x.read(adc), self.y.read(adc))
+    }
+
+    /// Reads both axes, returns the reading and the new direction if it
+    /// changed.
+    pub fn poll<A>(&mut self, adc: &mut A) -> (Reading, Option<Direction>)
+    where
+        X: AnalogChannel<A>,
+        Y: AnalogChannel<A>,
+    {
+        let (x_raw, y_raw) = self.read_raw(adc);
+        self.update(x_raw, y_raw)
+    }
+
+    /// Same as [`Joystick::poll`] with samples taken elsewhere.
+    pub fn update(&mut self, x_raw: u16, y_raw: u16) -> (Reading, Option<Direction>) {
+        let (x, y) = self.calibration.apply(x_raw, y_raw);
+        let reading = Reading {
+            x_raw,
+            y_raw,
+            x,
+            y,
+            angle: atan2(y, x),
+            magnitude: (isqrt((x * x + y * y) as u64) as u16).min(FULL_SCALE as u16),
+        };
+        (reading, self.debounce(self.classify(&reading)))
+    }
+
+    fn classify(&self, reading: &Reading) -> Direction {
+        let threshold = if self.direction == Direction::Center { ENGAGE } else { RELEASE };
+        if reading.magnitude < threshold {
+            return Direction::Center;
+        }
+        match self.direction.angle() {
+            Some(current) if angle_distance(reading.angle, current) <= 22 + HYSTERESIS => self.direction,
+            _ => Direction::from_angle(reading.angle),
+        }
+    }
+
+    fn debounce(&mut self, direction: Direction) -> Option<Direction> {
+        if direction == self.direction {
+            self.pending_count = 0;
+            return None;
+        }
+        if direction == self.pending {
+            self.pending_count += 1;
+        } else {
+            self.pending = direction;
+            self.pending_count = 1;
+        }
+        if self.pending_count < DEBOUNCE {
+            return None;
+        }
+        self.direction = direction;
+        self.pending_count = 0;
+        Some(direction)
+    }
+}
+
+/// Builds a [`Calibration`] from samples taken first with the stick at rest
+/// and then while it is swept around its whole range.
+pub struct Calibrator {
+    sum_x: u32,
+    sum_y: u32,
+    count: u32,
+    x: Axis,
+    y: Axis,
+}
+
+impl Calibrator {
+    pub fn new() -> Self {
+        let empty = Axis { min: u16::MAX, center: 0, max: 0 };
+        Calibrator { sum_x: 0, sum_y: 0, count: 0, x: empty, y: empty }
+    }
+
+    pub fn rest(&mut self, x_raw: u16, y_raw: u16) {
+        self.sum_x += x_raw as u32;
+        self.sum_y += y_raw as u32;
+        self.count += 1;
+        self.sweep(x_raw, y_raw);
+    }
+
+    pub fn sweep(&mut self, x_raw: u16, y_raw: u16) {
+        self.x.min = self.x.min.min(x_raw);
+        self.x.max = self.x.max.max(x_raw);
+        self.y.min = self.y.min.min(y_raw);
+        self.y.max = self.y.max.max(y_raw);
+    }
+
+    /// `None` when the samples do not make sense, e.g. the stick was not moved.
+    pub fn finish(&self, dead_zone: u16) -> Option<Calibration> {
+        if self.count == 0 {
+            return None;
+        }
+        let mut calibration = Calibration {
+            x: self.x,
+            y: self.y,
+            dead_zone,
+        };
+        calibration.x.center = (self.sum_x / self.count) as u16;
+        calibration.y.center = (self.sum_y / self.count) as u16;
+        Calibration::from_bytes(&calibration.to_bytes())
+    }
+}
+
+fn angle_distance(a: u16, b: u16) -> u16 {
+    let d = a.abs_diff(b);
+    d.min(360 - d)
+}
+
+/// Angle of `(x, y)` in whole degrees counter-clockwise from the positive X
+/// axis, 0..360, within a degree of the real thing.
+pub fn atan2(y: i32, x: i32) -> u16 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let (ax, ay) = (x.abs(), y.abs());
+    // atan(t) ≈ 45t + 15.6t(1 - t) degrees for 0 <= t <= 1, t in thousandths.
+    let t = ax.min(ay) * 1000 / ax.max(ay);
+    let octant = (45 * t + 156 * t * (1000 - t) / 10_000 + 500) / 1000;
+    let first = if ay > ax { 90 - octant } else { octant };
+    let angle = match (x >= 0, y >= 0) {
+        (true, true) => first,
+        (false, true) => 180 - first,
+        (false, false) => 180 + first,
+        (true, false) => 360 - first,
+    };
+    (angle % 360) as u16
+}
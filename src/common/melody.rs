@@ -0,0 +1,157 @@
+//! Melodies in the RTTTL ringtone format.
+//!
+//! `name:d=4,o=5,b=120:8c6,8e6,4g6,p,2c7` is a name, the default duration,
+//! octave and tempo, then the notes. A note is an optional duration (1 is a
+//! whole note, 32 the shortest), the letter or `p` for a pause, `#` for a
+//! sharp, an optional octave and `.` to make it half again as long.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Note {
+    /// Hz, 0 is a pause.
+    pub frequency: u16,
+    /// ms
+    pub duration: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Missing one of the three `:` separated sections.
+    Sections,
+    Setting,
+    /// The note at this index (counting from 0) does not parse.
+    Note(usize),
+}
+
+// Octave 8 from C, lower octaves halve it.
+const OCTAVE_8: [u16; 12] = [4186, 4435, 4699, 4978, 5274, 5588, 5920, 6272, 6645, 7040, 7459, 7902];
+
+#[derive(Clone)]
+pub struct Notes<'a> {
+    notes: core::str::Split<'a, char>,
+    duration: u8,
+    octave: u8,
+    // ms of a whole note
+    whole: u32,
+}
+
+/// Checks the whole melody and returns its notes.
+pub fn parse(text: &str) -> Result<Notes<'_>, Error> {
+    let mut sections = text.splitn(3, ':');
+    let (Some(_name), Some(settings), Some(notes)) = (sections.next(), sections.next(), sections.next()) else {
+        return Err(Error::Sections);
+    };
+
+    let (mut duration, mut octave, mut bpm) = (4u8, 6u8, 63u16);
+    for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (key, value) = setting.split_once('=').ok_or(Error::Setting)?;
+        let value: u16 = value.trim().parse().map_err(|_| Error::Setting)?;
+        match key.trim() {
+            "d" if is_duration(value) => duration = value as u8,
+            "o" if (4..=8).contains(&value) => octave = value as u8,
+            "b" if value > 0 => bpm = value,
+            _ => return Err(Error::Setting),
+        }
+    }
+
+    let melody = Notes {
+        notes: notes.split(','),
+        duration,
+        octave,
+        whole: 4 * 60_000 / bpm as u32,
+    };
+    for (i, note) in melody.notes.clone().enumerate() {
+        melody.note(note).ok_or(Error::Note(i))?;
+    }
+    Ok(melody)
+}
+
+impl Notes<'_> {
+    fn note(&self, text: &str) -> Option<Note> {
+        let text = text.trim().as_bytes();
+        let mut i = 0;
+
+        let digits = |i: &mut usize| {
+            let start = *i;
+            let mut value: u16 = 0;
+            while *i < text.len() && text[*i].is_ascii_digit() {
+                value = value.checked_mul(10)?.checked_add((text[*i] - b'0') as u16)?;
+                *i += 1;
+            }
+            Some((*i > start).then_some(value))
+        };
+
+        let duration = match digits(&mut i)? {
+            Some(value) if is_duration(value) => value as u8,
+            Some(_) => return None,
+            None => self.duration,
+        };
+
+        let semitone = match text.get(i)?.to_ascii_lowercase() {
+            b'c' => Some(0),
+            b'd' => Some(2),
+            b'e' => Some(4),
+            b'f' => Some(5),
+            b'g' => Some(7),
+            b'a' => Some(9),
+            b'b' | b'h' => Some(11),
+            b'p' => None,
+            _ => return None,
+        };
+        i += 1;
+
+        let sharp = text.get(i) == Some(&b'#');
+        if sharp {
+            i += 1;
+        }
+        let mut dotted = text.get(i) == Some(&b'.');
+        if dotted {
+            i += 1;
+        }
+        let octave = match digits(&mut i)? {
+            Some(value) if (4..=8).contains(&value) => value as u8,
+            Some(_) => return None,
+            None => self.octave,
+        };
+        if text.get(i) == Some(&b'.') {
+            dotted = true;
+            i += 1;
+        }
+        if i != text.len() {
+            return None;
+        }
+
+        let mut ms = self.whole / duration as u32;
+        if dotted {
+            ms += ms / 2;
+        }
+        let frequency = match semitone {
+            Some(semitone) => {
+                let index = semitone + sharp as usize;
+                let (index, octave) = if index == 12 { (0, octave + 1) } else { (index, octave) };
+                // B# of octave 8 is out of the table, keep it there.
+                OCTAVE_8[index] >> 8u8.saturating_sub(octave)
+            }
+            None => 0,
+        };
+        Some(Note { frequency, duration: ms.min(u16::MAX as u32) as u16 })
+    }
+
+    /// Length of the whole melody in ms.
+    pub fn duration(&self) -> u32 {
+        self.clone().map(|note| note.duration as u32).sum()
+    }
+}
+
+impl Iterator for Notes<'_> {
+    type Item = Note;
+
+    fn next(&mut self) -> Option<Note> {
+        // Checked by `parse` already.
+        let text = self.notes.next()?;
+        self.note(text)
+    }
+}
+
+fn is_duration(value: u16) -> bool {
+    matches!(value, 1 | 2 | 4 | 8 | 16 | 32)
+}
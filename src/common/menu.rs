@@ -0,0 +1,261 @@
+//! Menus on a character display, declared as a constant tree of items and
+//! walked with four buttons.
+//!
+//! Up and down move the cursor, select enters a submenu, opens an action or
+//! edits a value and back goes one level up. Every level keeps its cursor,
+//! so going back lands on the item the submenu was entered from. Editors
+//! change a copy of the value, select keeps it and back throws it away.
+
+use super::fixed;
+
+pub const COLUMNS: u8 = 16;
+pub const ROWS: u8 = 2;
+/// How deep submenus can nest, the top level included.
+pub const DEPTH: usize = 4;
+/// Values the editors can refer to, `id`s go from 0 below this.
+pub const VALUES: usize = 16;
+
+/// What a menu draws on, implemented for the I2C LCD and by the host tests.
+pub trait Screen {
+    fn clear(&mut self);
+    fn set_position(&mut self, col: u8, row: u8);
+    fn print(&mut self, text: &str);
+}
+
+#[cfg(target_arch = "avr")]
+impl<T, D> Screen for ag_lcd::LcdDisplay<T, D>
+where
+    T: embedded_hal::digital::OutputPin,
+    D: embedded_hal::delay::DelayNs,
+{
+    fn clear(&mut self) {
+        ag_lcd::LcdDisplay::clear(self);
+    }
+
+    fn set_position(&mut self, col: u8, row: u8) {
+        ag_lcd::LcdDisplay::set_position(self, col, row);
+    }
+
+    fn print(&mut self, text: &str) {
+        ag_lcd::LcdDisplay::print(self, text);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Item<'a> {
+    Submenu { label: &'a str, items: &'a [Item<'a>] },
+    /// Hands the display over to the program until back is pressed.
+    Action { label: &'a str, id: u8 },
+    /// Value `id` in `step`s from `min` to `max`, shown divided by 10 to the
+    /// power of `decimals`.
+    Number { label: &'a str, id: u8, min: i16, max: i16, step: i16, decimals: u8 },
+    /// Value `id` is an index into `options`.
+    Choice { label: &'a str, id: u8, options: &'a [&'a str] },
+    /// Value `id` is 0 or 1, select flips it right away.
+    Toggle { label: &'a str, id: u8 },
+}
+
+impl<'a> Item<'a> {
+    pub fn label(&self) -> &'a str {
+        match *self {
+            Item::Submenu { label, .. }
+            | Item::Action { label, .. }
+            | Item::Number { label, .. }
+            | Item::Choice { label, .. }
+            | Item::Toggle { label, .. } => label,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Up,
+    Down,
+    Select,
+    Back,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// The menu changed and needs drawing again.
+    Moved,
+    /// Action `id` has the display now.
+    Opened(u8),
+    /// Action `id` gave the display back.
+    Closed(u8),
+    /// Value `id` has a new value.
+    Changed(u8),
+}
+
+#[derive(Clone, Copy)]
+struct Level<'a> {
+    items: &'a [Item<'a>],
+    selected: usize,
+    // First item on the display.
+    top: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browsing,
+    Open(u8),
+    Editing(i16),
+}
+
+pub struct Menu<'a> {
+    levels: [Level<'a>; DEPTH],
+    depth: usize,
+    mode: Mode,
+    values: [i16; VALUES],
+}
+
+impl<'a> Menu<'a> {
+    pub fn new(items: &'a [Item<'a>]) -> Self {
+        Menu {
+            levels: [Level { items, selected: 0, top: 0 }; DEPTH],
+            depth: 1,
+            mode: Mode::Browsing,
+            values: [0; VALUES],
+        }
+    }
+
+    pub fn value(&self, id: u8) -> i16 {
+        self.values[id as usize]
+    }
+
+    pub fn set_value(&mut self, id: u8, value: i16) {
+        self.values[id as usize] = value;
+    }
+
+    /// The action that has the display, if any.
+    pub fn open(&self) -> Option<u8> {
+        match self.mode {
+            Mode::Open(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The item under the cursor.
+    pub fn selected(&self) -> &'a Item<'a> {
+        let level = self.levels[self.depth - 1];
+        &level.items[level.selected]
+    }
+
+    /// Handles a button press, `None` when nothing changed.
+    pub fn press(&mut self, key: Key) -> Option<Event> {
+        match self.mode {
+            Mode::Browsing => self.browse(key),
+            Mode::Open(id) => {
+                if key != Key::Back {
+                    return None;
+                }
+                self.mode = Mode::Browsing;
+                Some(Event::Closed(id))
+            }
+            Mode::Editing(value) => self.edit(key, value),
+        }
+    }
+
+    fn browse(&mut self, key: Key) -> Option<Event> {
+        let level = &mut self.levels[self.depth - 1];
+        match key {
+            Key::Up if level.selected > 0 => {
+                level.selected -= 1;
+                level.top = level.top.min(level.selected);
+            }
+            Key::Down if level.selected + 1 < level.items.len() => {
+                level.selected += 1;
+                level.top = level.top.max(level.selected + 1 - ROWS as usize);
+            }
+            Key::Select => match *self.selected() {
+                Item::Submenu { items, .. } if self.depth < DEPTH && !items.is_empty() => {
+                    // Coming back to the submenu left last finds its cursor
+                    // where it was.
+                    if !core::ptr::eq(self.levels[self.depth].items, items) {
+                        self.levels[self.depth] = Level { items, selected: 0, top: 0 };
+                    }
+                    self.depth += 1;
+                }
+                Item::Submenu { .. } => return None,
+                Item::Action { id, .. } => {
+                    self.mode = Mode::Open(id);
+                    return Some(Event::Opened(id));
+                }
+                Item::Number { id, .. } | Item::Choice { id, .. } => self.mode = Mode::Editing(self.value(id)),
+                Item::Toggle { id, .. } => {
+                    self.values[id as usize] ^= 1;
+                    return Some(Event::Changed(id));
+                }
+            },
+            Key::Back if self.depth > 1 => self.depth -= 1,
+            _ => return None,
+        }
+        Some(Event::Moved)
+    }
+
+    fn edit(&mut self, key: Key, value: i16) -> Option<Event> {
+        let value = match (*self.selected(), key) {
+            (Item::Number { max, step, .. }, Key::Up) => value.saturating_add(step).min(max),
+            (Item::Number { min, step, .. }, Key::Down) => value.saturating_sub(step).max(min),
+            (Item::Choice { options, .. }, Key::Up) => (value + 1) % options.len() as i16,
+            (Item::Choice { options, .. }, Key::Down) => (value + options.len() as i16 - 1) % options.len() as i16,
+            (Item::Number { id, .. } | Item::Choice { id, .. }, Key::Select) => {
+                self.mode = Mode::Browsing;
+                self.values[id as usize] = value;
+                return Some(Event::Changed(id));
+            }
+            _ => {
+                self.mode = Mode::Browsing;
+                return Some(Event::Moved);
+            }
+        };
+        self.mode = Mode::Editing(value);
+        Some(Event::Moved)
+    }
+
+    /// Draws the menu, leaves the display alone while an action has it.
+    pub fn render(&self, screen: &mut impl Screen) {
+        let mut buf = [0; fixed::MAX_LEN];
+        match self.mode {
+            Mode::Open(_) => {}
+            Mode::Editing(value) => {
+                screen.clear();
+                screen.set_position(0, 0);
+                screen.print(self.selected().label());
+                screen.set_position(0, 1);
+                screen.print("< ");
+                screen.print(value_text(self.selected(), value, &mut buf));
+                screen.print(" >");
+            }
+            Mode::Browsing => {
+                screen.clear();
+                let level = self.levels[self.depth - 1];
+                for (row, index) in (level.top..level.items.len()).take(ROWS as usize).enumerate() {
+                    let item = &level.items[index];
+                    screen.set_position(0, row as u8);
+                    screen.print(if index == level.selected { ">" } else { " " });
+                    screen.print(item.label());
+
+                    let value = match *item {
+                        Item::Number { id, .. } | Item::Choice { id, .. } | Item::Toggle { id, .. } => self.value(id),
+                        _ => continue,
+                    };
+                    // Right-aligned over whatever of the label does not fit.
+                    let text = value_text(item, value, &mut buf);
+                    screen.set_position(COLUMNS.saturating_sub(text.len() as u8), row as u8);
+                    screen.print(text);
+                }
+            }
+        }
+    }
+}
+
+fn value_text<'b>(item: &Item<'b>, value: i16, buf: &'b mut [u8; fixed::MAX_LEN]) -> &'b str {
+    match *item {
+        Item::Number { decimals, .. } => fixed::format(value as i32, decimals, buf),
+        Item::Choice { options, .. } => options.get(value as usize).copied().unwrap_or("?"),
+        Item::Toggle { .. } if value != 0 => "on",
+        Item::Toggle { .. } => "off",
+        _ => "",
+    }
+}
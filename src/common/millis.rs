@@ -0,0 +1,58 @@
+//! Millisecond counter on timer 0.
+//!
+//! It defines the `TIMER0_COMPA` interrupt handler, so instead of being part
+//! of `common` it is included on its own by the programs that need it:
+//!
+//! ```ignore
+//! #![feature(abi_avr_interrupt)]
+//!
+//! #[path = "../common/millis.rs"]
+//! mod millis;
+//! ```
+
+use avr_device::interrupt::Mutex;
+use core::cell::Cell;
+
+// 16 MHz / 64 = 250 kHz, so 250 timer counts make a millisecond.
+const TIMER_COUNTS: u8 = 250;
+
+static MILLIS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Starts the counter, interrupts still have to be enabled afterwards.
+pub fn init(tc0: arduino_hal::pac::TC0) {
+    tc0.tccr0a.write(|w| w.wgm0().ctc());
+    tc0.ocr0a.write(|w| w.bits(TIMER_COUNTS - 1));
+    tc0.tccr0b.write(|w| w.cs0().prescale_64());
+    tc0.timsk0.write(|w| w.ocie0a().set_bit());
+
+    avr_device::interrupt::free(|cs| MILLIS.borrow(cs).set(0));
+}
+
+/// Milliseconds since [`init`], wraps after about 49 days.
+pub fn millis() -> u32 {
+    avr_device::interrupt::free(|cs| MILLIS.borrow(cs).get())
+}
+
+/// Microseconds since [`init`] in steps of 4 µs, wraps after about 71 minutes.
+pub fn micros() -> u32 {
+    avr_device::interrupt::free(|cs| {
+        let tc0 = unsafe { &*arduino_hal::pac::TC0::ptr() };
+        let mut millis = MILLIS.borrow(cs).get();
+        let mut counts = tc0.tcnt0.read().bits();
+        // The counter may have wrapped after interrupts were disabled, the
+        // handler then has not counted that millisecond yet.
+        if tc0.tifr0.read().ocf0a().bit_is_set() {
+            millis = millis.wrapping_add(1);
+            counts = tc0.tcnt0.read().bits();
+        }
+        millis.wrapping_mul(1000).wrapping_add(counts as u32 * 4)
+    })
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn TIMER0_COMPA() {
+    avr_device::interrupt::free(|cs| {
+        let counter = MILLIS.borrow(cs);
+        counter.set(counter.get().wrapping_add(1));
+    });
+}
@@ -0,0 +1,26 @@
+//! Code shared between the exercise programs.
+//!
+//! Firmware pulls it in with `#[path = "../common/mod.rs"] mod common;`, host
+//! tools include the individual hardware-independent files directly.
+//! `millis.rs` has an interrupt handler and is included separately,
+//! `report.rs` needs `std` and is only for the host tools.
+#![allow(dead_code)]
+
+pub mod analog;
+pub mod animation;
+pub mod color;
+pub mod crc;
+pub mod fixed;
+pub mod gamma;
+pub mod joystick;
+pub mod melody;
+pub mod menu;
+pub mod morse;
+#[cfg(target_arch = "avr")]
+pub mod persist;
+pub mod random;
+pub mod rgb_led;
+pub mod stats;
+pub mod telemetry;
+#[cfg(target_arch = "avr")]
+pub mod tone;
@@ -0,0 +1,319 @@
+//! Morse alphabet and timing shared by the transmitter and the receivers.
+//!
+//! Only `core` is used here so the same file builds for the board and on the
+//! host.
+
+pub const DOT_DURATION: u16 = 200;
+pub const DASH_DURATION: u16 = DOT_DURATION * 3;
+pub const SYMBOL_SPACE: u16 = DOT_DURATION;
+pub const LETTER_SPACE: u16 = DOT_DURATION * 3;
+pub const WORD_SPACE: u16 = DOT_DURATION * 7;
+
+pub const MORSE_ALPHABET: &[(&str, &str)] = &[
+    ("A", ".-"),
+    ("B", "-..."),
+    ("C", "-.-."),
+    ("D", "-.."),
+    ("E", "."),
+    ("F", "..-."),
+    ("G", "--."),
+    ("H", "...."),
+    ("I", ".."),
+    ("J", ".---"),
+    ("K", "-.-"),
+    ("L", ".-.."),
+    ("M", "--"),
+    ("N", "-."),
+    ("O", "---"),
+    ("P", ".--."),
+    ("Q", "--.-"),
+    ("R", ".-."),
+    ("S", "..."),
+    ("T", "-"),
+    ("U", "..-"),
+    ("V", "...-"),
+    ("W", ".--"),
+    ("X", "-..-"),
+    ("Y", "-.--"),
+    ("Z", "--.."),
+    ("0", "-----"),
+    ("1", ".----"),
+    ("2", "..---"),
+    ("3", "...--"),
+    ("4", "....-"),
+    ("5", "....."),
+    ("6", "-...."),
+    ("7", "--..."),
+    ("8", "---.."),
+    ("9", "----."),
+    (".", ".-.-.-"),
+    (",", "--..--"),
+    ("/", "-..-."),
+    ("?", "..--.."),
+];
+
+/// Printed for a received code that is not in the alphabet.
+pub const UNKNOWN: char = '*';
+
+/// Longest code the decoder keeps, longer ones decode as [`UNKNOWN`].
+const MAX_CODE: usize = 6;
+
+/// Ambient light must differ from a lit LED by at least this many ADC counts.
+const MIN_CONTRAST: u16 = 40;
+
+/// A mark longer than this many dots is a change of ambient light, not Morse.
+const STUCK_MARK_DOTS: u16 = 8;
+
+pub fn encode(c: char) -> Option<&'static str> {
+    let c = c.to_ascii_uppercase();
+    MORSE_ALPHABET
+        .iter()
+        .find(|(letter, _)| letter.starts_with(c))
+        .map(|(_, code)| *code)
+}
+
+/// One step of a transmission, the LED (or tone) is on or off for `duration` ms.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Element {
+    pub on: bool,
+    pub duration: u16,
+}
+
+/// Calls `f` with the steps the transmitter takes to send `c`.
+///
+/// Every dot and dash is followed by a symbol space and the last one also by a
+/// letter space, a `' '` is a word space on top of that. Returns `false` for
+/// characters that have no code.
+pub fn send_char(c: char, mut f: impl FnMut(Element)) -> bool {
+    if c == ' ' {
+        f(Element { on: false, duration: WORD_SPACE });
+        return true;
+    }
+    let Some(code) = encode(c) else {
+        return false;
+    };
+    for symbol in code.bytes() {
+        let duration = if symbol == b'.' { DOT_DURATION } else { DASH_DURATION };
+        f(Element { on: true, duration });
+        f(Element { on: false, duration: SYMBOL_SPACE });
+    }
+    f(Element { on: false, duration: LETTER_SPACE });
+    true
+}
+
+pub fn decode(code: &str) -> Option<char> {
+    MORSE_ALPHABET
+        .iter()
+        .find(|(_, c)| *c == code)
+        .and_then(|(letter, _)| letter.chars().next())
+}
+
+/// Turns mark and space durations (in ms) into text.
+///
+/// Marks shorter than two dots are dots, anything longer is a dash. A space of
+/// two dots ends the letter and one of five dots ends the word, which sits
+/// between the 3/7 dot gaps of the standard and the 4/11 dot gaps our
+/// transmitter produces.
+pub struct Decoder {
+    dot: u16,
+    code: [u8; MAX_CODE],
+    len: usize,
+    overflow: bool,
+    word_pending: bool,
+}
+
+impl Decoder {
+    pub fn new(dot: u16) -> Self {
+        Decoder {
+            dot,
+            code: [0; MAX_CODE],
+            len: 0,
+            overflow: false,
+            word_pending: false,
+        }
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// The light was on for `duration` ms.
+    pub fn mark(&mut self, duration: u16) {
+        let symbol = if duration < self.dot * 2 { b'.' } else { b'-' };
+        if self.len < MAX_CODE {
+            self.code[self.len] = symbol;
+            self.len += 1;
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    /// The light has been off for `duration` ms so far.
+    ///
+    /// Returns the finished letter once the space is long enough and then a
+    /// single `' '` when it grows into a word space.
+    pub fn space(&mut self, duration: u16) -> Option<char> {
+        if self.len > 0 || self.overflow {
+            if duration >= self.dot * 2 {
+                return Some(self.finish_letter());
+            }
+        } else if self.word_pending && duration >= self.dot * 5 {
+            self.word_pending = false;
+            return Some(' ');
+        }
+        None
+    }
+
+    /// Ends the letter being received, if any, regardless of timing.
+    pub fn flush(&mut self) -> Option<char> {
+        if self.len > 0 || self.overflow {
+            Some(self.finish_letter())
+        } else {
+            None
+        }
+    }
+
+    fn finish_letter(&mut self) -> char {
+        let letter = if self.overflow {
+            UNKNOWN
+        } else {
+            core::str::from_utf8(&self.code[..self.len])
+                .ok()
+                .and_then(decode)
+                .unwrap_or(UNKNOWN)
+        };
+        self.len = 0;
+        self.overflow = false;
+        self.word_pending = true;
+        letter
+    }
+}
+
+/// Decides from raw light sensor samples whether the LED is lit.
+///
+/// Tracks the ambient (`low`) and lit (`high`) levels with a moving average and
+/// switches half way between them with some hysteresis, so it adapts to the
+/// room without any calibration. Levels are kept multiplied by 16.
+pub struct LightDetector {
+    low: u16,
+    high: u16,
+    last: u16,
+    lit: bool,
+    primed: bool,
+    inverted: bool,
+}
+
+impl LightDetector {
+    /// `inverted` is for dividers where more light gives a lower reading.
+    pub fn new(inverted: bool) -> Self {
+        LightDetector {
+            low: 0,
+            high: 0,
+            last: 0,
+            lit: false,
+            primed: false,
+            inverted,
+        }
+    }
+
+    /// Feeds one 10-bit ADC sample and returns whether the LED is lit.
+    pub fn sample(&mut self, raw: u16) -> bool {
+        let raw = raw.min(1023);
+        let value = if self.inverted { 1023 - raw } else { raw } << 4;
+        self.last = value;
+
+        if !self.primed {
+            self.low = value;
+            self.high = value;
+            self.primed = true;
+        }
+
+        let contrast = (self.high - self.low).max(MIN_CONTRAST << 4);
+        let threshold = self.low + contrast / 2;
+        let hysteresis = contrast / 8;
+
+        if self.lit {
+            if value < threshold.saturating_sub(hysteresis) {
+                self.lit = false;
+            }
+        } else if value > threshold + hysteresis {
+            self.lit = true;
+        }
+
+        if self.lit {
+            self.high = average(self.high, value);
+        } else {
+            self.low = average(self.low, value);
+        }
+        self.high = self.high.max(self.low);
+
+        self.lit
+    }
+
+    /// Takes the current level as the new ambient light.
+    pub fn rebase(&mut self) {
+        self.low = self.last;
+        self.high = self.last;
+        self.lit = false;
+    }
+}
+
+fn average(avg: u16, value: u16) -> u16 {
+    avg - (avg >> 3) + (value >> 3)
+}
+
+/// Light sensor samples in, decoded text out.
+pub struct OpticalReceiver {
+    detector: LightDetector,
+    decoder: Decoder,
+    period: u16,
+    run: u16,
+    lit: bool,
+}
+
+impl OpticalReceiver {
+    /// Expects one sample every `period` ms from a transmitter sending with a
+    /// dot of `dot` ms.
+    pub fn new(period: u16, dot: u16, inverted: bool) -> Self {
+        OpticalReceiver {
+            detector: LightDetector::new(inverted),
+            decoder: Decoder::new(dot),
+            period,
+            run: 0,
+            lit: false,
+        }
+    }
+
+    pub fn sample(&mut self, raw: u16) -> Option<char> {
+        let lit = self.detector.sample(raw);
+        if lit != self.lit {
+            if self.lit {
+                self.decoder.mark(self.run);
+            }
+            self.lit = lit;
+            self.run = 0;
+        }
+        self.run = self.run.saturating_add(self.period);
+
+        if !self.lit {
+            return self.decoder.space(self.run);
+        }
+
+        if self.run > self.decoder.dot() * STUCK_MARK_DOTS {
+            self.detector.rebase();
+            self.lit = false;
+            self.run = 0;
+        }
+        None
+    }
+
+    /// Ends the reception, returns the letter still being received.
+    pub fn finish(&mut self) -> Option<char> {
+        if self.lit {
+            self.decoder.mark(self.run);
+            self.lit = false;
+            self.run = 0;
+        }
+        self.decoder.flush()
+    }
+}
@@ -0,0 +1,44 @@
+//! Settings kept in the EEPROM across resets.
+//!
+//! A record is the raw bytes followed by their CRC-8. A blank or corrupted
+//! record fails to load and the program falls back to its defaults.
+
+use arduino_hal::eeprom::Eeprom;
+
+use super::crc::crc8;
+
+/// Bytes a record of `len` bytes takes up in the EEPROM.
+pub const fn record_size(len: usize) -> u16 {
+    len as u16 + 1
+}
+
+pub fn load(eep: &mut Eeprom, addr: u16, data: &mut [u8]) -> bool {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = eep.read_byte(addr + i as u16);
+    }
+    let checksum = eep.read_byte(addr + data.len() as u16);
+
+    let blank = checksum == 0xFF && data.iter().all(|&b| b == 0xFF);
+    !blank && checksum == crc8(data)
+}
+
+pub fn store(eep: &mut Eeprom, addr: u16, data: &[u8]) {
+    for (i, &byte) in data.iter().enumerate() {
+        update_byte(eep, addr + i as u16, byte);
+    }
+    update_byte(eep, addr + data.len() as u16, crc8(data));
+}
+
+/// Invalidates the record so the next `load` fails.
+pub fn erase(eep: &mut Eeprom, addr: u16, len: usize) {
+    for i in 0..record_size(len) {
+        update_byte(eep, addr + i, 0xFF);
+    }
+}
+
+// EEPROM cells wear out, only write the ones that change.
+fn update_byte(eep: &mut Eeprom, addr: u16, byte: u8) {
+    if eep.read_byte(addr) != byte {
+        eep.write_byte(addr, byte);
+    }
+}
@@ -0,0 +1,76 @@
+//! Pseudo-random numbers for the games.
+//!
+//! [`Rng`] is xorshift32 with a multiplied output, small and fast enough for
+//! the AVR and good enough for picking colors and delays. [`Entropy`] mixes
+//! whatever unpredictable values the board can gather into its seed.
+
+/// Collects noisy values into a seed.
+pub struct Entropy {
+    pool: u32,
+    count: u16,
+}
+
+impl Entropy {
+    pub fn new() -> Self {
+        Entropy { pool: 0, count: 0 }
+    }
+
+    /// Mixes `value` in, every bit of it affects every bit of the seed.
+    pub fn add(&mut self, value: u32) {
+        self.pool = mix(self.pool.rotate_left(7) ^ value ^ self.count as u32);
+        self.count = self.count.wrapping_add(1);
+    }
+
+    pub fn seed(&self) -> u32 {
+        mix(self.pool ^ 0x9E37_79B9)
+    }
+}
+
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // Xorshift never leaves zero, `mix` maps only zero to zero.
+        let state = mix(seed);
+        Rng { state: if state == 0 { 0x9E37_79B9 } else { state } }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x.wrapping_mul(0x2C1B_3C6D)
+    }
+
+    /// Uniform number in `0..n` without the modulo bias, `n` must not be 0.
+    pub fn below(&mut self, n: u32) -> u32 {
+        // Lemire's multiply and shift, redrawing the few values that would
+        // make some results more likely.
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let product = self.next_u32() as u64 * n as u64;
+            if product as u32 >= threshold {
+                return (product >> 32) as u32;
+            }
+        }
+    }
+
+    /// Uniform number in `min..=max`.
+    pub fn range(&mut self, min: u16, max: u16) -> u16 {
+        min + self.below((max - min) as u32 + 1) as u16
+    }
+}
+
+// Finalizer of MurmurHash3.
+fn mix(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xC2B2_AE35);
+    x ^= x >> 16;
+    x
+}
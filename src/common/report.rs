@@ -0,0 +1,35 @@
+//! Pass/fail lines of the checks the host tools run.
+
+/// Prints a line per check and remembers whether all of them passed.
+pub struct Report {
+    passed: bool,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Report { passed: true }
+    }
+
+    pub fn check(&mut self, name: &str, ok: bool) {
+        println!("{:<36} {:>12}  {}", name, "", verdict(ok));
+        self.passed &= ok;
+    }
+
+    /// A check with the `value` it measured.
+    pub fn value(&mut self, name: &str, value: f64, ok: bool) {
+        println!("{:<36} {:>12.5}  {}", name, value, verdict(ok));
+        self.passed &= ok;
+    }
+
+    pub fn passed(&self) -> bool {
+        self.passed
+    }
+}
+
+fn verdict(ok: bool) -> &'static str {
+    if ok {
+        "ok"
+    } else {
+        "FAIL"
+    }
+}
@@ -0,0 +1,207 @@
+//! RGB LED on PWM or plain digital pins.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::pwm::SetDutyCycle;
+
+use super::color::Rgb;
+use super::gamma::{correct, LEVEL_MAX};
+
+// Fades are done in steps of this many ms.
+const FADE_STEP: u16 = 10;
+
+/// One color of the LED.
+pub trait Channel {
+    /// Highest duty cycle, 1 for a channel that is only on or off.
+    fn max_duty(&self) -> u16;
+    fn set_duty(&mut self, duty: u16);
+}
+
+/// Channel on a PWM output, e.g. `pins.d9.into_output().into_pwm(&timer1)`.
+pub struct Pwm<P>(pub P);
+
+impl<P: SetDutyCycle> Channel for Pwm<P> {
+    fn max_duty(&self) -> u16 {
+        self.0.max_duty_cycle()
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.0.set_duty_cycle(duty).ok();
+    }
+}
+
+/// Channel on a digital output. It turns on from half the light output up,
+/// or works as software PWM with dithering refreshed often enough.
+pub struct Digital<P>(pub P);
+
+impl<P: OutputPin> Channel for Digital<P> {
+    fn max_duty(&self) -> u16 {
+        1
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        if duty > 0 {
+            self.0.set_high().ok();
+        } else {
+            self.0.set_low().ok();
+        }
+    }
+}
+
+/// Colors go through the perceptual correction from `gamma` unless turned
+/// off with [`RgbLed::with_gamma`].
+///
+/// The corrected levels are finer than the 8-bit timers. With dithering on,
+/// [`RgbLed::refresh`] alternates between the two nearest duty cycles so the
+/// average comes out right, which needs it to be called every millisecond or
+/// so. Without it the level is just rounded.
+///
+/// For a common anode LED, where a channel lights up when its pin is low, use
+/// [`RgbLed::with_active_low`].
+pub struct RgbLed<R, G, B> {
+    r: R,
+    g: G,
+    b: B,
+    color: Rgb,
+    levels: [u16; 3],
+    error: [u32; 3],
+    gamma: bool,
+    dithering: bool,
+    active_low: bool,
+}
+
+impl<R, G, B> RgbLed<Pwm<R>, Pwm<G>, Pwm<B>>
+where
+    R: SetDutyCycle,
+    G: SetDutyCycle,
+    B: SetDutyCycle,
+{
+    pub fn pwm(r: R, g: G, b: B) -> Self {
+        RgbLed::new(Pwm(r), Pwm(g), Pwm(b))
+    }
+}
+
+impl<R, G, B> RgbLed<Digital<R>, Digital<G>, Digital<B>>
+where
+    R: OutputPin,
+    G: OutputPin,
+    B: OutputPin,
+{
+    pub fn digital(r: R, g: G, b: B) -> Self {
+        RgbLed::new(Digital(r), Digital(g), Digital(b))
+    }
+}
+
+impl<R, G, B> RgbLed<R, G, B>
+where
+    R: Channel,
+    G: Channel,
+    B: Channel,
+{
+    pub fn new(r: R, g: G, b: B) -> Self {
+        let mut led = RgbLed {
+            r,
+            g,
+            b,
+            color: Rgb::OFF,
+            levels: [0; 3],
+            error: [0; 3],
+            gamma: true,
+            dithering: false,
+            active_low: false,
+        };
+        led.set(Rgb::OFF);
+        led
+    }
+
+    pub fn with_gamma(mut self, gamma: bool) -> Self {
+        self.gamma = gamma;
+        self.set(self.color);
+        self
+    }
+
+    pub fn with_dithering(mut self, dithering: bool) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    pub fn with_active_low(mut self, active_low: bool) -> Self {
+        self.active_low = active_low;
+        self.set(self.color);
+        self
+    }
+
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+
+    pub fn set(&mut self, color: Rgb) {
+        self.color = color;
+        self.levels = [color.r, color.g, color.b].map(|c| self.level(c));
+        self.refresh();
+    }
+
+    pub fn off(&mut self) {
+        self.set(Rgb::OFF);
+    }
+
+    /// Writes the duty cycles, the next dithering step if it is on.
+    pub fn refresh(&mut self) {
+        let [r, g, b] = self.levels;
+        let (dithering, active_low) = (self.dithering, self.active_low);
+        write(&mut self.r, r, &mut self.error[0], dithering, active_low);
+        write(&mut self.g, g, &mut self.error[1], dithering, active_low);
+        write(&mut self.b, b, &mut self.error[2], dithering, active_low);
+    }
+
+    /// Fades from the current color to `to` over `duration` ms, blocking.
+    pub fn fade(&mut self, to: Rgb, duration: u16, delay: &mut impl DelayNs) {
+        let from = self.color;
+        let steps = (duration / FADE_STEP).max(1);
+        for step in 1..=steps {
+            self.set(from.mix(to, (step as u32 * 255 / steps as u32) as u8));
+            delay.delay_ms(FADE_STEP as u32);
+        }
+    }
+
+    /// Blinks `color` `times` times and leaves the LED off, blocking.
+    pub fn blink(&mut self, color: Rgb, on: u16, off: u16, times: u8, delay: &mut impl DelayNs) {
+        for _ in 0..times {
+            self.set(color);
+            delay.delay_ms(on as u32);
+            self.off();
+            delay.delay_ms(off as u32);
+        }
+    }
+
+    /// Plays `pattern` of `(color, ms)` steps, blocking.
+    pub fn play(&mut self, pattern: &[(Rgb, u16)], delay: &mut impl DelayNs) {
+        for &(color, duration) in pattern {
+            self.set(color);
+            delay.delay_ms(duration as u32);
+        }
+    }
+
+    fn level(&self, value: u8) -> u16 {
+        if self.gamma {
+            correct(value)
+        } else {
+            value as u16 * LEVEL_MAX / 255
+        }
+    }
+}
+
+fn write<C: Channel>(channel: &mut C, level: u16, error: &mut u32, dithering: bool, active_low: bool) {
+    let max = channel.max_duty();
+    let scaled = level as u32 * max as u32;
+    let duty = if dithering {
+        // First order sigma-delta, the remainder is carried to the next step.
+        *error += scaled;
+        let duty = *error / LEVEL_MAX as u32;
+        *error %= LEVEL_MAX as u32;
+        duty as u16
+    } else {
+        ((scaled + LEVEL_MAX as u32 / 2) / LEVEL_MAX as u32) as u16
+    };
+    channel.set_duty(if active_low { max - duty } else { duty });
+}
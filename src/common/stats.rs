@@ -0,0 +1,55 @@
+//! Running statistics of a series of measurements, without storing them.
+
+use super::fixed::isqrt;
+
+#[derive(Clone, Copy)]
+pub struct Stats {
+    count: u32,
+    sum: u64,
+    sum_squares: u64,
+    min: u32,
+    max: u32,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Stats {
+            count: 0,
+            sum: 0,
+            sum_squares: 0,
+            min: u32::MAX,
+            max: 0,
+        }
+    }
+
+    pub fn add(&mut self, value: u32) {
+        self.count += 1;
+        self.sum += value as u64;
+        self.sum_squares += value as u64 * value as u64;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<u32> {
+        (self.count > 0).then(|| (self.sum / self.count as u64) as u32)
+    }
+
+    pub fn min(&self) -> Option<u32> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// Population standard deviation.
+    pub fn std_dev(&self) -> Option<u32> {
+        let mean = self.mean()? as u64;
+        let variance = (self.sum_squares / self.count as u64).saturating_sub(mean * mean);
+        Some(isqrt(variance))
+    }
+}
@@ -0,0 +1,234 @@
+//! Binary telemetry frames, an opt-in alternative to printing values as text.
+//!
+//! A frame is `kind, sequence, timestamp (u32), payload, CRC-16` with all
+//! numbers little endian, COBS encoded and terminated by a zero byte. The
+//! sequence number wraps at 256 and lets the receiver count lost frames.
+//!
+//! The same file is used by the firmware to encode and by the host tools to
+//! decode.
+
+use super::crc::crc16;
+
+/// Longest encoded frame, including the terminating zero.
+pub const MAX_FRAME: usize = 16;
+
+const HEADER: usize = 6;
+const MAX_RAW: usize = HEADER + 4 + 2;
+
+const KIND_JOYSTICK: u8 = 1;
+const KIND_ANALOG: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Record {
+    /// Raw joystick axes.
+    Joystick { x: u16, y: u16 },
+    /// Any other analog input, `channel` is the ADC channel.
+    Analog { channel: u8, value: u16 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sample {
+    pub sequence: u8,
+    /// Milliseconds since the board started.
+    pub timestamp: u32,
+    pub record: Record,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Longer than any valid frame.
+    Overflow,
+    /// Broken COBS encoding or a frame of the wrong length.
+    Framing,
+    Checksum,
+    UnknownKind(u8),
+}
+
+pub struct Encoder {
+    sequence: u8,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder { sequence: 0 }
+    }
+
+    /// Encodes `record` into `out` and returns the length of the frame.
+    pub fn encode(&mut self, timestamp: u32, record: &Record, out: &mut [u8; MAX_FRAME]) -> usize {
+        let mut raw = [0u8; MAX_RAW];
+        raw[1] = self.sequence;
+        raw[2..HEADER].copy_from_slice(&timestamp.to_le_bytes());
+        let len = match *record {
+            Record::Joystick { x, y } => {
+                raw[0] = KIND_JOYSTICK;
+                raw[HEADER..HEADER + 2].copy_from_slice(&x.to_le_bytes());
+                raw[HEADER + 2..HEADER + 4].copy_from_slice(&y.to_le_bytes());
+                HEADER + 4
+            }
+            Record::Analog { channel, value } => {
+                raw[0] = KIND_ANALOG;
+                raw[HEADER] = channel;
+                raw[HEADER + 1..HEADER + 3].copy_from_slice(&value.to_le_bytes());
+                HEADER + 3
+            }
+        };
+        let crc = crc16(&raw[..len]);
+        raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let encoded = cobs_encode(&raw[..len + 2], out);
+        out[encoded] = 0;
+        encoded + 1
+    }
+
+    /// Encodes `record` and hands the frame to `write` byte by byte.
+    pub fn send(&mut self, timestamp: u32, record: &Record, mut write: impl FnMut(u8)) {
+        let mut frame = [0u8; MAX_FRAME];
+        let len = self.encode(timestamp, record, &mut frame);
+        for &byte in &frame[..len] {
+            write(byte);
+        }
+    }
+}
+
+/// Turns a byte stream back into samples, one byte at a time.
+pub struct Decoder {
+    buffer: [u8; MAX_FRAME],
+    len: usize,
+    overflow: bool,
+    last_sequence: Option<u8>,
+    lost: u32,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder {
+            buffer: [0; MAX_FRAME],
+            len: 0,
+            overflow: false,
+            last_sequence: None,
+            lost: 0,
+        }
+    }
+
+    /// Starts over for an encoder that was created anew, its sequence
+    /// numbers begin again from 0.
+    pub fn reset(&mut self) {
+        *self = Decoder::new();
+    }
+
+    /// Frames missing according to the sequence numbers, including those
+    /// that arrived damaged.
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// Returns the decoded frame when `byte` ends one.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Sample, Error>> {
+        if byte != 0 {
+            if self.len < MAX_FRAME {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        let len = self.len;
+        let overflow = self.overflow;
+        self.len = 0;
+        self.overflow = false;
+
+        if len == 0 {
+            return None;
+        }
+        if overflow {
+            return Some(Err(Error::Overflow));
+        }
+
+        let mut raw = [0u8; MAX_FRAME];
+        let result = cobs_decode(&self.buffer[..len], &mut raw)
+            .ok_or(Error::Framing)
+            .and_then(|raw_len| parse(&raw[..raw_len]));
+
+        if let Ok(sample) = result {
+            if let Some(last) = self.last_sequence {
+                self.lost += sample.sequence.wrapping_sub(last).wrapping_sub(1) as u32;
+            }
+            self.last_sequence = Some(sample.sequence);
+        }
+        Some(result)
+    }
+}
+
+fn parse(raw: &[u8]) -> Result<Sample, Error> {
+    if raw.len() < HEADER + 2 {
+        return Err(Error::Framing);
+    }
+    let (data, crc) = raw.split_at(raw.len() - 2);
+    if crc16(data) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(Error::Checksum);
+    }
+
+    let payload = &data[HEADER..];
+    let u16_at = |i: usize| u16::from_le_bytes([payload[i], payload[i + 1]]);
+    let record = match data[0] {
+        KIND_JOYSTICK if payload.len() == 4 => Record::Joystick { x: u16_at(0), y: u16_at(2) },
+        KIND_ANALOG if payload.len() == 3 => Record::Analog { channel: payload[0], value: u16_at(1) },
+        KIND_JOYSTICK | KIND_ANALOG => return Err(Error::Framing),
+        kind => return Err(Error::UnknownKind(kind)),
+    };
+
+    Ok(Sample {
+        sequence: data[1],
+        timestamp: u32::from_le_bytes([data[2], data[3], data[4], data[5]]),
+        record,
+    })
+}
+
+/// COBS encodes `data` into `out` without the terminating zero, returns the
+/// encoded length. `out` must have room for `data.len() + 1` bytes, frames
+/// here are far below the 254 bytes where more overhead would be needed.
+fn cobs_encode(data: &[u8], out: &mut [u8]) -> usize {
+    let mut code_at = 0;
+    let mut code = 1u8;
+    let mut len = 1;
+    for &byte in data {
+        if byte == 0 {
+            out[code_at] = code;
+            code_at = len;
+            len += 1;
+            code = 1;
+        } else {
+            out[len] = byte;
+            len += 1;
+            code += 1;
+        }
+    }
+    out[code_at] = code;
+    len
+}
+
+fn cobs_decode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    let mut len = 0;
+    while read < data.len() {
+        let code = data[read] as usize;
+        if code == 0 || read + code > data.len() {
+            return None;
+        }
+        read += 1;
+        for _ in 1..code {
+            *out.get_mut(len)? = data[read];
+            read += 1;
+            len += 1;
+        }
+        if read < data.len() {
+            *out.get_mut(len)? = 0;
+            len += 1;
+        }
+    }
+    Some(len)
+}
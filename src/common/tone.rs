@@ -0,0 +1,201 @@
+//! Non-blocking tones and melodies on a buzzer.
+//!
+//! An 8-bit timer interrupts at twice the frequency and the handler toggles
+//! the pin, so any pin works. Letting the timer toggle its output on compare
+//! match would save the interrupts, but only OC0A (D6) and OC2A (D11) can be
+//! driven that way, and the buzzer of the kit sits on D7 while D11 dims the
+//! green LED. At most that is some 16 000 short interrupts a second for the
+//! highest note.
+//!
+//! The program owns the interrupt vector and forwards it, which lets it pick
+//! whichever timer it has free:
+//!
+//! ```ignore
+//! common::tone::init(dp.TC2, pins.d7.into_output().downgrade());
+//!
+//! #[avr_device::interrupt(atmega328p)]
+//! fn TIMER2_COMPA() {
+//!     common::tone::interrupt();
+//! }
+//! ```
+
+use arduino_hal::pac::{TC0, TC2};
+use arduino_hal::port::{mode::Output, Pin};
+use avr_device::interrupt::Mutex;
+use core::cell::RefCell;
+use heapless::Deque;
+
+use super::melody::Note;
+
+/// Notes that can be queued at once, the rest of a longer melody is dropped.
+pub const MAX_NOTES: usize = 32;
+
+const CLOCK: u32 = 16_000_000;
+const PRESCALERS: [u32; 5] = [1, 8, 64, 256, 1024];
+// Interrupt rate while a pause is playing.
+const PAUSE_RATE: u32 = 1000;
+
+/// Timer that interrupts on compare match A every `top + 1` counts of the
+/// clock divided by `PRESCALERS[prescaler]`.
+pub trait ToneTimer {
+    fn start(prescaler: usize, top: u8);
+    fn stop();
+}
+
+impl ToneTimer for TC0 {
+    fn start(prescaler: usize, top: u8) {
+        let tc0 = unsafe { &*TC0::ptr() };
+        tc0.tccr0a.write(|w| w.wgm0().ctc());
+        tc0.ocr0a.write(|w| w.bits(top));
+        tc0.tcnt0.write(|w| w.bits(0));
+        tc0.tccr0b.write(|w| match prescaler {
+            0 => w.cs0().direct(),
+            1 => w.cs0().prescale_8(),
+            2 => w.cs0().prescale_64(),
+            3 => w.cs0().prescale_256(),
+            _ => w.cs0().prescale_1024(),
+        });
+        tc0.timsk0.write(|w| w.ocie0a().set_bit());
+    }
+
+    fn stop() {
+        let tc0 = unsafe { &*TC0::ptr() };
+        tc0.timsk0.write(|w| w.ocie0a().clear_bit());
+        tc0.tccr0b.write(|w| w.cs0().no_clock());
+    }
+}
+
+impl ToneTimer for TC2 {
+    fn start(prescaler: usize, top: u8) {
+        let tc2 = unsafe { &*TC2::ptr() };
+        tc2.tccr2a.write(|w| w.wgm2().ctc());
+        tc2.ocr2a.write(|w| w.bits(top));
+        tc2.tcnt2.write(|w| w.bits(0));
+        tc2.tccr2b.write(|w| match prescaler {
+            0 => w.cs2().direct(),
+            1 => w.cs2().prescale_8(),
+            2 => w.cs2().prescale_64(),
+            3 => w.cs2().prescale_256(),
+            _ => w.cs2().prescale_1024(),
+        });
+        tc2.timsk2.write(|w| w.ocie2a().set_bit());
+    }
+
+    fn stop() {
+        let tc2 = unsafe { &*TC2::ptr() };
+        tc2.timsk2.write(|w| w.ocie2a().clear_bit());
+        tc2.tccr2b.write(|w| w.cs2().no_clock());
+    }
+}
+
+struct Buzzer {
+    pin: Pin<Output>,
+    start: fn(usize, u8),
+    stop: fn(),
+    queue: Deque<Note, MAX_NOTES>,
+    // Interrupts left of the current note.
+    remaining: u32,
+    sounding: bool,
+}
+
+impl Buzzer {
+    fn next(&mut self) {
+        self.pin.set_low();
+        match self.queue.pop_front() {
+            Some(note) => {
+                let rate = match note.frequency {
+                    0 => PAUSE_RATE,
+                    frequency => 2 * frequency as u32,
+                };
+                let (prescaler, top) = timer_setting(rate);
+                (self.start)(prescaler, top);
+                self.sounding = note.frequency != 0;
+                self.remaining = (note.duration as u32 * rate / 1000).max(1);
+            }
+            None => {
+                (self.stop)();
+                self.remaining = 0;
+            }
+        }
+    }
+}
+
+static BUZZER: Mutex<RefCell<Option<Buzzer>>> = Mutex::new(RefCell::new(None));
+
+/// Takes over `timer` for the buzzer on `pin`. Interrupts still have to be
+/// enabled and the timer's compare match A handler has to call [`interrupt`].
+pub fn init<T: ToneTimer>(_timer: T, pin: Pin<Output>) {
+    T::stop();
+    let buzzer = Buzzer {
+        pin,
+        start: T::start,
+        stop: T::stop,
+        queue: Deque::new(),
+        remaining: 0,
+        sounding: false,
+    };
+    avr_device::interrupt::free(|cs| BUZZER.borrow(cs).replace(Some(buzzer)));
+}
+
+/// Plays `frequency` Hz for `duration` ms, replacing whatever was playing.
+pub fn play(frequency: u16, duration: u16) {
+    play_notes([Note { frequency, duration }]);
+}
+
+/// Plays the notes one after another, replacing whatever was playing.
+pub fn play_notes(notes: impl IntoIterator<Item = Note>) {
+    avr_device::interrupt::free(|cs| {
+        if let Some(buzzer) = BUZZER.borrow(cs).borrow_mut().as_mut() {
+            buzzer.queue.clear();
+            for note in notes {
+                if buzzer.queue.push_back(note).is_err() {
+                    break;
+                }
+            }
+            buzzer.next();
+        }
+    });
+}
+
+pub fn stop() {
+    avr_device::interrupt::free(|cs| {
+        if let Some(buzzer) = BUZZER.borrow(cs).borrow_mut().as_mut() {
+            buzzer.queue.clear();
+            buzzer.next();
+        }
+    });
+}
+
+pub fn is_playing() -> bool {
+    avr_device::interrupt::free(|cs| match BUZZER.borrow(cs).borrow().as_ref() {
+        Some(buzzer) => buzzer.remaining > 0,
+        None => false,
+    })
+}
+
+/// Has to be called from the compare match A interrupt of the timer.
+pub fn interrupt() {
+    avr_device::interrupt::free(|cs| {
+        if let Some(buzzer) = BUZZER.borrow(cs).borrow_mut().as_mut() {
+            if buzzer.remaining > 1 {
+                buzzer.remaining -= 1;
+                if buzzer.sounding {
+                    buzzer.pin.toggle();
+                }
+            } else {
+                buzzer.next();
+            }
+        }
+    });
+}
+
+// Smallest prescaler that fits the interrupt rate into the 8-bit counter.
+fn timer_setting(rate: u32) -> (usize, u8) {
+    for (i, &prescaler) in PRESCALERS.iter().enumerate() {
+        let counts = CLOCK / prescaler / rate;
+        if counts <= 256 {
+            return (i, (counts.max(1) - 1) as u8);
+        }
+    }
+    (PRESCALERS.len() - 1, u8::MAX)
+}
@@ -0,0 +1,138 @@
+//! Koch method lessons: start with two characters at full speed and add the
+//! next one from [`KOCH_ORDER`] once the current ones are copied reliably.
+
+pub const KOCH_ORDER: &[u8] = b"KMRSUAPTLOWI.NJEF0Y,VG5/Q9ZH38B?427C1D6X";
+
+pub const GROUP_LEN: usize = 5;
+pub const PROGRESS_SIZE: usize = 1 + 2 * KOCH_ORDER.len();
+
+const START_LEVEL: u8 = 2;
+const MIN_ATTEMPTS: u8 = 10;
+const PASS_PERCENT: u8 = 90;
+// Halve the counters at this point so old mistakes are forgotten eventually.
+const DECAY_AT: u8 = 50;
+
+#[derive(Clone, Copy, Default)]
+pub struct Stat {
+    pub sent: u8,
+    pub correct: u8,
+}
+
+impl Stat {
+    /// Whole percent, rounded down, for showing.
+    pub fn accuracy(&self) -> u8 {
+        if self.sent == 0 {
+            0
+        } else {
+            (self.correct as u16 * 100 / self.sent as u16) as u8
+        }
+    }
+
+    fn record(&mut self, correct: bool) {
+        if self.sent >= DECAY_AT {
+            self.sent /= 2;
+            self.correct /= 2;
+        }
+        self.sent += 1;
+        if correct {
+            self.correct += 1;
+        }
+    }
+}
+
+pub struct Progress {
+    level: u8,
+    stats: [Stat; KOCH_ORDER.len()],
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress {
+            level: START_LEVEL,
+            stats: [Stat::default(); KOCH_ORDER.len()],
+        }
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Characters the student is practicing, the newest one last.
+    pub fn active(&self) -> &'static [u8] {
+        &KOCH_ORDER[..self.level as usize]
+    }
+
+    pub fn stat(&self, index: usize) -> Stat {
+        self.stats[index]
+    }
+
+    /// Random group from the active characters, `rand` is any 32-bit generator.
+    pub fn group(&self, mut rand: impl FnMut() -> u32) -> [u8; GROUP_LEN] {
+        let active = self.active();
+        let mut group = [0; GROUP_LEN];
+        for c in group.iter_mut() {
+            *c = active[(rand() >> 8) as usize % active.len()];
+        }
+        group
+    }
+
+    /// Compares what was typed with what was sent, position by position, and
+    /// returns the number of correct characters. Missing characters are wrong.
+    pub fn score(&mut self, sent: &[u8], typed: &[u8]) -> u8 {
+        let mut correct = 0;
+        for (i, &c) in sent.iter().enumerate() {
+            let ok = typed.get(i).map(|t| t.to_ascii_uppercase()) == Some(c);
+            if let Some(index) = KOCH_ORDER.iter().position(|&k| k == c) {
+                self.stats[index].record(ok);
+            }
+            if ok {
+                correct += 1;
+            }
+        }
+        correct
+    }
+
+    /// Adds the next character when every active one was copied often enough
+    /// with more than 90 % accuracy. Returns the new character.
+    pub fn try_advance(&mut self) -> Option<u8> {
+        if self.level as usize >= KOCH_ORDER.len() {
+            return None;
+        }
+        let ready = self.stats[..self.level as usize]
+            .iter()
+            // Exactly, the rounded down percentage would fail 10 of 11.
+            .all(|s| s.sent >= MIN_ATTEMPTS && s.correct as u16 * 100 > s.sent as u16 * PASS_PERCENT as u16);
+        if !ready {
+            return None;
+        }
+        for stat in self.stats.iter_mut() {
+            *stat = Stat::default();
+        }
+        self.level += 1;
+        Some(KOCH_ORDER[self.level as usize - 1])
+    }
+
+    pub fn to_bytes(&self) -> [u8; PROGRESS_SIZE] {
+        let mut bytes = [0; PROGRESS_SIZE];
+        bytes[0] = self.level;
+        for (i, stat) in self.stats.iter().enumerate() {
+            bytes[1 + 2 * i] = stat.sent;
+            bytes[2 + 2 * i] = stat.correct;
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; PROGRESS_SIZE]) -> Option<Self> {
+        let level = bytes[0];
+        if level < START_LEVEL || level as usize > KOCH_ORDER.len() {
+            return None;
+        }
+        let mut progress = Progress::new();
+        progress.level = level;
+        for (i, stat) in progress.stats.iter_mut().enumerate() {
+            stat.sent = bytes[1 + 2 * i];
+            stat.correct = bytes[2 + 2 * i].min(stat.sent);
+        }
+        Some(progress)
+    }
+}
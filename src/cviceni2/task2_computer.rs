@@ -0,0 +1,340 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::time::Duration;
+
+#[path = "../common/morse.rs"]
+mod morse;
+#[allow(dead_code)]
+#[path = "../common/report.rs"]
+mod report;
+
+use morse::{send_char, Decoder, Element, LightDetector, OpticalReceiver, DOT_DURATION};
+use report::Report;
+
+const SAMPLE_RATE: u32 = 8000;
+const TONE_HZ: f32 = 700.0;
+const RAMP_MS: u32 = 5;
+// Audio is decoded by feeding its envelope in steps of this many ms to the
+// same receiver the light sensor firmware uses.
+const ENVELOPE_MS: u16 = 5;
+// The light sensor firmware samples this often, in ms.
+const SAMPLE_PERIOD: u16 = 10;
+
+const USAGE: &str = "Usage:
+  task2_computer encode <text> [out.csv|out.wav]
+  task2_computer decode <in.csv|in.wav>
+  task2_computer send <port> <text>
+  task2_computer receiver";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("encode") if args.len() == 3 || args.len() == 4 => {
+            let elements = timing(&args[2]);
+            match args.get(3) {
+                Some(path) if path.ends_with(".wav") => fs::write(path, to_wav(&elements))?,
+                Some(path) => fs::write(path, to_csv(&elements))?,
+                None => print!("{}", to_csv(&elements)),
+            }
+        }
+        Some("decode") if args.len() == 3 => {
+            let path = &args[2];
+            let data = fs::read(path)?;
+            let text = if path.ends_with(".wav") {
+                decode_wav(&data)?
+            } else {
+                decode_csv(&String::from_utf8(data)?)?
+            };
+            println!("{}", text.trim_end());
+        }
+        Some("send") if args.len() == 4 => send(&args[2], &args[3])?,
+        Some("receiver") if args.len() == 2 => {
+            if !check_receiver() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Steps the board takes to send `text`, neighbouring gaps merged into one.
+fn timing(text: &str) -> Vec<Element> {
+    let mut elements: Vec<Element> = Vec::new();
+    for c in text.chars() {
+        let known = send_char(c, |element| match elements.last_mut() {
+            Some(last) if !last.on && !element.on => last.duration += element.duration,
+            _ => elements.push(element),
+        });
+        if !known {
+            eprintln!("No Morse code for {:?}, skipping", c);
+        }
+    }
+    elements
+}
+
+fn to_csv(elements: &[Element]) -> String {
+    let mut csv = String::from("on,ms\n");
+    for element in elements {
+        csv.push_str(&format!("{},{}\n", element.on as u8, element.duration));
+    }
+    csv
+}
+
+fn decode_csv(csv: &str) -> Result<String, Box<dyn Error>> {
+    let mut decoder = Decoder::new(DOT_DURATION);
+    let mut text = String::new();
+    let mut space: u16 = 0;
+
+    for (number, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("on") {
+            continue;
+        }
+        let (on, ms) = line
+            .split_once(',')
+            .ok_or_else(|| format!("line {}: expected `on,ms`", number + 1))?;
+        let ms: u16 = ms.trim().parse()?;
+
+        if on.trim() == "1" {
+            decoder.mark(ms);
+            space = 0;
+        } else {
+            space = space.saturating_add(ms);
+            while let Some(c) = decoder.space(space) {
+                text.push(c);
+            }
+        }
+    }
+    text.extend(decoder.flush());
+
+    Ok(text)
+}
+
+fn to_wav(elements: &[Element]) -> Vec<u8> {
+    let mut samples: Vec<i16> = Vec::new();
+    let ramp = (SAMPLE_RATE * RAMP_MS / 1000) as usize;
+
+    for element in elements {
+        let count = (SAMPLE_RATE * element.duration as u32 / 1000) as usize;
+        for i in 0..count {
+            if !element.on {
+                samples.push(0);
+                continue;
+            }
+            // Fade in and out so the tone does not click.
+            let edge = i.min(count - 1 - i).min(ramp) as f32 / ramp as f32;
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let value = (2.0 * std::f32::consts::PI * TONE_HZ * t).sin() * edge * 16000.0;
+            samples.push(value as i16);
+        }
+    }
+
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+fn decode_wav(wav: &[u8]) -> Result<String, Box<dyn Error>> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return Err("not a WAV file".into());
+    }
+
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut data: Option<&[u8]> = None;
+    let mut pos = 12;
+    while pos + 8 <= wav.len() {
+        let id = &wav[pos..pos + 4];
+        let len = u32::from_le_bytes(wav[pos + 4..pos + 8].try_into()?) as usize;
+        let body = &wav[pos + 8..(pos + 8 + len).min(wav.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                format = Some((
+                    u16::from_le_bytes([body[0], body[1]]),
+                    u16::from_le_bytes([body[2], body[3]]),
+                    u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+                    u16::from_le_bytes([body[14], body[15]]),
+                ));
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        pos += 8 + len + (len & 1);
+    }
+
+    let (kind, channels, rate, bits) = format.ok_or("missing fmt chunk")?;
+    let data = data.ok_or("missing data chunk")?;
+    if kind != 1 || bits != 16 || channels == 0 {
+        return Err("only 16-bit PCM is supported".into());
+    }
+
+    let frame = channels as usize * 2;
+    let window = (rate as usize * ENVELOPE_MS as usize / 1000).max(1);
+
+    let mut envelope: Vec<u32> = Vec::new();
+    for chunk in data.chunks(frame * window) {
+        let sum: u32 = chunk
+            .chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]).unsigned_abs() as u32)
+            .sum();
+        envelope.push(sum / (chunk.len() as u32 / 2).max(1));
+    }
+
+    let peak = envelope.iter().copied().max().unwrap_or(0).max(1);
+    let mut receiver = OpticalReceiver::new(ENVELOPE_MS, DOT_DURATION, false);
+    let mut text = String::new();
+
+    // A little silence first so the receiver learns the background level.
+    let lead_in = std::iter::repeat_n(0, 20);
+    for level in lead_in.chain(envelope) {
+        text.extend(receiver.sample((level * 1023 / peak) as u16));
+    }
+    text.extend(receiver.finish());
+
+    Ok(text)
+}
+
+fn send(port: &str, text: &str) -> Result<(), Box<dyn Error>> {
+    let mut port = serialport::new(port, 57600)
+        .timeout(Duration::from_millis(10))
+        .open()?;
+
+    // Opening the port resets the board.
+    std::thread::sleep(Duration::from_secs(2));
+
+    let mut stdout = io::stdout();
+    for c in text.chars() {
+        let mut duration: u64 = 0;
+        if !send_char(c, |element| duration += element.duration as u64) {
+            eprintln!("No Morse code for {:?}, skipping", c);
+            continue;
+        }
+
+        // The board only buffers one character, wait until it is flashed.
+        let mut buf = [0u8; 4];
+        port.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+        print!("{}", c);
+        stdout.flush()?;
+        std::thread::sleep(Duration::from_millis(duration));
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Light sensor readings of `text` being flashed, one every
+/// [`SAMPLE_PERIOD`] ms. `light` gives the reading from the time in ms and
+/// whether the LED is on.
+fn light_samples(text: &str, mut light: impl FnMut(u32, bool) -> u16) -> Vec<u16> {
+    // Dark before and after, so the receiver learns the background and the
+    // last letter ends.
+    let dark = Element { on: false, duration: 2000 };
+    let mut samples = Vec::new();
+    let mut time = 0;
+    for element in std::iter::once(dark).chain(timing(text)).chain(std::iter::once(dark)) {
+        for _ in 0..element.duration / SAMPLE_PERIOD {
+            samples.push(light(time, element.on));
+            time += SAMPLE_PERIOD as u32;
+        }
+    }
+    samples
+}
+
+fn receive(samples: &[u16], inverted: bool) -> String {
+    let mut receiver = OpticalReceiver::new(SAMPLE_PERIOD, DOT_DURATION, inverted);
+    let mut text: String = samples.iter().filter_map(|&raw| receiver.sample(raw)).collect();
+    text.extend(receiver.finish());
+    text.trim_end().to_string()
+}
+
+/// Noise in -`amplitude`..=`amplitude`, from a fixed seed so runs repeat.
+fn noise(amplitude: u16) -> impl FnMut() -> i32 {
+    let mut state = 0x2545_F491u32;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state % (2 * amplitude as u32 + 1)) as i32 - amplitude as i32
+    }
+}
+
+fn reading(level: i32) -> u16 {
+    level.clamp(0, 1023) as u16
+}
+
+/// Feeds the receiver the light sensor readings it would see in a few rooms,
+/// returns whether it decoded all of them.
+fn check_receiver() -> bool {
+    let mut report = Report::new();
+    const TEXT: &str = "PARIS 73 SOS?";
+
+    let clean = light_samples(TEXT, |_, on| if on { 700 } else { 300 });
+    report.check("clean signal", receive(&clean, false) == TEXT);
+
+    // Noise just under half the minimum contrast, where a dark room would
+    // start to flicker.
+    let mut jitter = noise(15);
+    let noisy = light_samples(TEXT, |_, on| reading(if on { 400 } else { 300 } + jitter()));
+    report.check("noisy signal", receive(&noisy, false) == TEXT);
+
+    // Ambient light doubles over the message, as in a room at dawn.
+    let mut jitter = noise(10);
+    let drifting = light_samples(TEXT, |time, on| reading(250 + time as i32 / 100 + if on { 200 } else { 0 } + jitter()));
+    let drift = drifting.last().copied().unwrap_or(0) as i32 - drifting[0] as i32;
+    report.check("drifting ambient light", drift > 200 && receive(&drifting, false) == TEXT);
+
+    let mut jitter = noise(15);
+    let inverted = light_samples(TEXT, |_, on| reading(if on { 200 } else { 800 } + jitter()));
+    report.check("inverted divider", receive(&inverted, true) == TEXT);
+
+    // The room light goes on in a long pause, the receiver takes it for a
+    // stuck mark at first.
+    let mut jitter = noise(10);
+    let switched = light_samples("SOS  SOS", |time, on| {
+        let room = if time < 9600 { 200 } else { 500 };
+        reading(room + if on { 200 } else { 0 } + jitter())
+    });
+    report.check("room light switched on", receive(&switched, false) == "SOS SOS");
+
+    // Readings around the threshold must not make the detector chatter.
+    let mut detector = LightDetector::new(false);
+    let mut jitter = noise(8);
+    for _ in 0..100 {
+        detector.sample(reading(300 + jitter()));
+    }
+    let mut lit = detector.sample(reading(700));
+    let mut changes = 0;
+    for _ in 0..500 {
+        let now = detector.sample(reading(700 + jitter()));
+        changes += (now != lit) as u32;
+        lit = now;
+    }
+    report.check("steady light does not flicker", lit && changes == 0);
+    report.check("flicker under the minimum contrast", {
+        let mut detector = LightDetector::new(false);
+        (0..500).all(|i| !detector.sample(reading(300 + if i % 40 < 20 { 15 } else { 0 })))
+    });
+
+    report.passed()
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::morse::{OpticalReceiver, DOT_DURATION};
+
+// Photoresistor on the weather station board (A1), brighter = higher reading.
+const SAMPLE_PERIOD: u16 = 10;
+const LIGHT_INVERTED: bool = false;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+    let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
+    let light = pins.a1.into_analog_input(&mut adc);
+
+    let mut receiver = OpticalReceiver::new(SAMPLE_PERIOD, DOT_DURATION, LIGHT_INVERTED);
+
+    ufmt::uwriteln!(&mut serial, "Listening...").unwrap();
+
+    loop {
+        let light_value = light.analog_read(&mut adc);
+
+        if let Some(c) = receiver.sample(light_value) {
+            ufmt::uwrite!(&mut serial, "{}", c).unwrap();
+        }
+
+        arduino_hal::delay_ms(SAMPLE_PERIOD);
+    }
+}
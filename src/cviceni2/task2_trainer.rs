@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+
+use arduino_hal::eeprom::Eeprom;
+use arduino_hal::hal::port::{PB3, PD7};
+use arduino_hal::port::mode::Output;
+use arduino_hal::port::Pin;
+use arduino_hal::prelude::*;
+use panic_halt as _;
+
+#[path = "../common/mod.rs"]
+mod common;
+mod koch;
+
+use common::morse::{send_char, Element};
+use common::persist;
+use koch::{Progress, GROUP_LEN, PROGRESS_SIZE};
+
+const PROGRESS_ADDR: u16 = 0;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+    let mut eep = Eeprom::new(dp.EEPROM);
+
+    let mut led = pins.d11.into_output();
+    let mut buzzer = pins.d7.into_output();
+
+    let mut bytes = [0u8; PROGRESS_SIZE];
+    let mut progress = if persist::load(&mut eep, PROGRESS_ADDR, &mut bytes) {
+        Progress::from_bytes(&bytes).unwrap_or_else(Progress::new)
+    } else {
+        Progress::new()
+    };
+
+    ufmt::uwrite!(&mut serial, "Koch trainer, level {}: ", progress.level()).unwrap();
+    for &c in progress.active() {
+        ufmt::uwrite!(&mut serial, "{}", c as char).unwrap();
+    }
+    ufmt::uwriteln!(&mut serial, "").unwrap();
+    ufmt::uwriteln!(&mut serial, "Type what you hear and press Enter. Press any key to start.").unwrap();
+
+    // How long it takes to press the first key is the only randomness we have.
+    let mut seed: u32 = 0;
+    while serial.read().is_err() {
+        seed = seed.wrapping_add(1);
+    }
+    let mut rand = || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        seed
+    };
+
+    loop {
+        let group = progress.group(&mut rand);
+
+        arduino_hal::delay_ms(1000);
+        for &c in group.iter() {
+            send_char(c as char, |element| play(&mut led, &mut buzzer, element));
+        }
+
+        let mut line = [0u8; 16];
+        let mut len = 0;
+        loop {
+            match nb::block!(serial.read()) {
+                Ok(b'\r') | Ok(b'\n') => {
+                    if len > 0 {
+                        break;
+                    }
+                }
+                Ok(b) if len < line.len() && b != b' ' => {
+                    line[len] = b;
+                    len += 1;
+                    ufmt::uwrite!(&mut serial, "{}", b as char).unwrap();
+                }
+                _ => {}
+            }
+        }
+
+        let correct = progress.score(&group, &line[..len]);
+
+        ufmt::uwrite!(&mut serial, "\r\nsent ").unwrap();
+        for &c in group.iter() {
+            ufmt::uwrite!(&mut serial, "{}", c as char).unwrap();
+        }
+        ufmt::uwriteln!(&mut serial, ", {}/{} correct", correct, GROUP_LEN).unwrap();
+
+        for (i, &c) in progress.active().iter().enumerate() {
+            let stat = progress.stat(i);
+            ufmt::uwrite!(&mut serial, "{}:{}% ", c as char, stat.accuracy()).unwrap();
+        }
+        ufmt::uwriteln!(&mut serial, "").unwrap();
+
+        if let Some(c) = progress.try_advance() {
+            ufmt::uwriteln!(&mut serial, "New character: {}", c as char).unwrap();
+        }
+
+        persist::store(&mut eep, PROGRESS_ADDR, &progress.to_bytes());
+    }
+}
+
+// LED on and a 1 kHz tone on the buzzer, one period per millisecond.
+fn play(led: &mut Pin<Output, PB3>, buzzer: &mut Pin<Output, PD7>, element: Element) {
+    if !element.on {
+        arduino_hal::delay_ms(element.duration);
+        return;
+    }
+    led.set_high();
+    for _ in 0..element.duration {
+        buzzer.set_high();
+        arduino_hal::delay_us(500);
+        buzzer.set_low();
+        arduino_hal::delay_us(500);
+    }
+    led.set_low();
+}
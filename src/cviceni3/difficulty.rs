@@ -0,0 +1,93 @@
+//! Difficulty levels of the reaction game and how long each color is shown.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Easy,
+    Normal,
+    Hard,
+    /// Shows colors for shorter after hits and longer after misses.
+    Adaptive,
+}
+
+pub struct Settings {
+    pub rounds: u8,
+    /// Range of the display time in ms, the adaptive level starts at the top
+    /// of it and never leaves it.
+    pub min_display: u16,
+    pub max_display: u16,
+    /// Chance of a round where nothing may be pressed.
+    pub decoy_percent: u8,
+}
+
+impl Level {
+    pub const COUNT: usize = 4;
+
+    /// Level number `index` as in `level as u8`, anything past the last one
+    /// is the last one.
+    pub fn from_index(index: u8) -> Level {
+        match index {
+            0 => Level::Easy,
+            1 => Level::Normal,
+            2 => Level::Hard,
+            _ => Level::Adaptive,
+        }
+    }
+
+    pub fn settings(self) -> Settings {
+        match self {
+            Level::Easy => Settings { rounds: 5, min_display: 1200, max_display: 2000, decoy_percent: 0 },
+            Level::Normal => Settings { rounds: 5, min_display: 800, max_display: 1500, decoy_percent: 10 },
+            Level::Hard => Settings { rounds: 10, min_display: 400, max_display: 800, decoy_percent: 20 },
+            Level::Adaptive => Settings { rounds: 10, min_display: 300, max_display: 2000, decoy_percent: 15 },
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Easy => "easy",
+            Level::Normal => "normal",
+            Level::Hard => "hard",
+            Level::Adaptive => "adaptive",
+        }
+    }
+}
+
+pub struct Pacing {
+    level: Level,
+    settings: Settings,
+    // Longest display time of the adaptive level at the moment.
+    window: u16,
+}
+
+impl Pacing {
+    pub fn new(level: Level) -> Self {
+        let settings = level.settings();
+        let window = settings.max_display;
+        Pacing { level, settings, window }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Range to pick the next display time from.
+    pub fn window(&self) -> (u16, u16) {
+        match self.level {
+            Level::Adaptive => ((self.window * 3 / 4).max(self.settings.min_display), self.window),
+            _ => (self.settings.min_display, self.settings.max_display),
+        }
+    }
+
+    /// Adapts to the result of a color round, decoys do not count.
+    pub fn record(&mut self, correct: bool) {
+        if self.level != Level::Adaptive {
+            return;
+        }
+        self.window = if correct {
+            self.window - self.window / 8
+        } else {
+            self.window.saturating_add(self.window / 4)
+        }
+        .clamp(self.settings.min_display, self.settings.max_display);
+    }
+}
@@ -0,0 +1,184 @@
+//! Head-to-head mode of the reaction game.
+//!
+//! Red and green belong to the first player, blue and yellow to the second.
+//! After a random wait the LED shows magenta, meaning red or blue, or yellow,
+//! meaning green or yellow, and the first correct press takes the point.
+//! Presses come with the time they were captured at, so two presses seen in
+//! the same step are still told apart.
+
+use crate::common::color::Rgb;
+use crate::common::random::Rng;
+
+/// Points needed to win.
+pub const TARGET: u8 = 5;
+
+// Wait before the cue in ms.
+const MIN_WAIT: u16 = 1000;
+const MAX_WAIT: u16 = 3000;
+// In µs.
+const CUE_TIMEOUT: u32 = 2_000_000;
+// Presses this soon after a round ends are still from it, bounces mostly.
+const SETTLE: u32 = 300_000;
+
+pub const PLAYER_COLORS: [Rgb; 2] = [Rgb::RED, Rgb::BLUE];
+
+/// First or second button of each player.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Cue {
+    First,
+    Second,
+}
+
+impl Cue {
+    pub fn rgb(self) -> Rgb {
+        match self {
+            Cue::First => Rgb::MAGENTA,
+            Cue::Second => Rgb::YELLOW,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Action {
+    Show(Cue),
+    Clear,
+    /// Pressed before the cue, the other player gets the point.
+    FalseStart { player: u8 },
+    /// Pressed the other button, out for the rest of the round.
+    Wrong { player: u8 },
+    /// `reaction` in µs, `scores` after the point.
+    Point { player: u8, reaction: u32, scores: [u8; 2] },
+    /// Both pressed at the very same time, nobody scores.
+    Tie,
+    /// Nobody pressed the right button in time.
+    Timeout,
+    Winner { player: u8 },
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Next,
+    Waiting { since: u32, wait: u32 },
+    Cue { cue: Cue, shown: u32, out: [bool; 2] },
+    Over,
+}
+
+pub struct Duel {
+    rng: Rng,
+    scores: [u8; 2],
+    state: State,
+}
+
+impl Duel {
+    pub fn new(rng: Rng) -> Self {
+        Duel { rng, scores: [0; 2], state: State::Next }
+    }
+
+    pub fn scores(&self) -> [u8; 2] {
+        self.scores
+    }
+
+    pub fn is_over(&self) -> bool {
+        matches!(self.state, State::Over)
+    }
+
+    /// Advances to `now` (µs). `presses` has the capture time of each button
+    /// pressed since the previous step, in the order red, green, blue, yellow.
+    pub fn step(&mut self, now: u32, presses: [Option<u32>; 4], mut act: impl FnMut(Action)) {
+        match self.state {
+            State::Next => {
+                let wait = self.rng.range(MIN_WAIT, MAX_WAIT) as u32 * 1000;
+                self.state = State::Waiting { since: now, wait };
+            }
+            State::Waiting { since, wait } => {
+                let early = (0..2).find(|&player| {
+                    presses[player * 2..player * 2 + 2]
+                        .iter()
+                        .flatten()
+                        .any(|&at| (at.wrapping_sub(since) as i32) >= SETTLE as i32)
+                });
+                if let Some(player) = early {
+                    act(Action::FalseStart { player: player as u8 });
+                    self.point(1 - player as u8, 0, &mut act);
+                } else if now.wrapping_sub(since) >= wait {
+                    let cue = if self.rng.below(2) == 0 { Cue::First } else { Cue::Second };
+                    act(Action::Show(cue));
+                    self.state = State::Cue { cue, shown: now, out: [false; 2] };
+                }
+            }
+            State::Cue { cue, shown, mut out } => {
+                // Earliest right and wrong press of each player.
+                let mut right: [Option<u32>; 2] = [None; 2];
+                let mut wrong: [Option<u32>; 2] = [None; 2];
+                for (button, at) in presses.iter().enumerate() {
+                    let Some(at) = at else { continue };
+                    let player = button / 2;
+                    let elapsed = at.wrapping_sub(shown);
+                    if (elapsed as i32) < 0 {
+                        // Captured just before the LED lit up.
+                        act(Action::Clear);
+                        act(Action::FalseStart { player: player as u8 });
+                        self.point(1 - player as u8, 0, &mut act);
+                        return;
+                    }
+                    let slot = if (button % 2 == 0) == (cue == Cue::First) { &mut right } else { &mut wrong };
+                    slot[player] = Some(slot[player].map_or(elapsed, |earlier| earlier.min(elapsed)));
+                }
+
+                let mut best: [Option<u32>; 2] = [None; 2];
+                for player in 0..2 {
+                    if out[player] {
+                        continue;
+                    }
+                    match (right[player], wrong[player]) {
+                        (Some(right), wrong) if wrong.is_none_or(|wrong| right < wrong) => best[player] = Some(right),
+                        (_, Some(_)) => {
+                            out[player] = true;
+                            act(Action::Wrong { player: player as u8 });
+                        }
+                        _ => {}
+                    }
+                }
+
+                match best {
+                    [Some(first), Some(second)] if first == second => {
+                        act(Action::Clear);
+                        act(Action::Tie);
+                        self.state = State::Next;
+                    }
+                    [Some(first), Some(second)] => {
+                        let (player, reaction) = if first < second { (0, first) } else { (1, second) };
+                        act(Action::Clear);
+                        self.point(player, reaction, &mut act);
+                    }
+                    [Some(reaction), None] => {
+                        act(Action::Clear);
+                        self.point(0, reaction, &mut act);
+                    }
+                    [None, Some(reaction)] => {
+                        act(Action::Clear);
+                        self.point(1, reaction, &mut act);
+                    }
+                    [None, None] if out == [true, true] || now.wrapping_sub(shown) >= CUE_TIMEOUT => {
+                        act(Action::Clear);
+                        act(Action::Timeout);
+                        self.state = State::Next;
+                    }
+                    [None, None] => self.state = State::Cue { cue, shown, out },
+                }
+            }
+            State::Over => {}
+        }
+    }
+
+    fn point(&mut self, player: u8, reaction: u32, act: &mut impl FnMut(Action)) {
+        self.scores[player as usize] += 1;
+        act(Action::Point { player, reaction, scores: self.scores });
+        if self.scores[player as usize] >= TARGET {
+            act(Action::Winner { player });
+            self.state = State::Over;
+        } else {
+            self.state = State::Next;
+        }
+    }
+}
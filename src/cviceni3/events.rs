@@ -0,0 +1,42 @@
+//! Machine readable lines about the reaction game, for logging on a
+//! computer. Each event is one line, its name followed by `key=value`
+//! fields, e.g. `ROUND n=3 result=hit ms=254.312`. They are only sent after
+//! the computer asks for them with [`ENABLE`] and are the same in every
+//! language.
+
+pub const GAME_START: &str = "GAME_START";
+pub const ROUND: &str = "ROUND";
+pub const FOUL: &str = "FOUL";
+pub const GAME_END: &str = "GAME_END";
+
+/// Sent by the computer while the game waits for the start button.
+pub const ENABLE: u8 = b'E';
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Hit,
+    Miss,
+    /// Lost to a pre-pressed button or mashing.
+    Foul,
+}
+
+impl Outcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Hit => "hit",
+            Outcome::Miss => "miss",
+            Outcome::Foul => "foul",
+        }
+    }
+}
+
+/// Splits an event line into its name and fields, `None` for any other
+/// line.
+pub fn parse(line: &str) -> Option<(&str, impl Iterator<Item = (&str, &str)>)> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?;
+    if ![GAME_START, ROUND, FOUL, GAME_END].contains(&name) {
+        return None;
+    }
+    Some((name, words.filter_map(|word| word.split_once('='))))
+}
@@ -0,0 +1,112 @@
+//! Everything the games say on serial, in English or, with the `czech`
+//! feature, in Czech. The Czech texts leave out diacritics so they show up
+//! right in any serial monitor.
+
+pub struct Messages {
+    pub scores_cleared: &'static str,
+    pub choose_level: &'static str,
+    pub simon_start: &'static str,
+    pub duel_players: &'static str,
+    pub duel_cues: &'static str,
+    pub level: &'static str,
+    /// In the order of [`crate::difficulty::Level`].
+    pub levels: [&'static str; 4],
+    pub foul: &'static str,
+    /// In the order of [`crate::reaction::Foul`].
+    pub fouls: [&'static str; 3],
+    pub round: &'static str,
+    pub hit: &'static str,
+    pub miss: &'static str,
+    pub reaction: &'static str,
+    pub mean: &'static str,
+    pub best: &'static str,
+    pub worst: &'static str,
+    pub std_dev: &'static str,
+    pub all_fouls: &'static str,
+    pub new_high_score: &'static str,
+    pub initial: &'static str,
+    pub high_scores: &'static str,
+    pub won: &'static str,
+    pub lost: &'static str,
+    pub round_done: &'static str,
+    pub player: &'static str,
+    pub false_start: &'static str,
+    pub wrong_button: &'static str,
+    pub score: &'static str,
+    pub tie: &'static str,
+    pub too_slow: &'static str,
+    pub wins: &'static str,
+}
+
+pub const ENGLISH: Messages = Messages {
+    scores_cleared: "High scores cleared",
+    choose_level: "Press a button to start: red easy, green normal, blue hard, yellow adaptive",
+    simon_start: "Simon says, press any button to start",
+    duel_players: "Duel: player 1 red and green, player 2 blue and yellow",
+    duel_cues: "Magenta means red or blue, yellow green or yellow. Press to start",
+    level: "Level",
+    levels: ["easy", "normal", "hard", "adaptive"],
+    foul: "Foul",
+    fouls: ["pre-pressed", "false start", "mashing"],
+    round: "Round",
+    hit: "hit",
+    miss: "missed",
+    reaction: "Reaction",
+    mean: "mean",
+    best: "best",
+    worst: "worst",
+    std_dev: "std dev",
+    all_fouls: "Fouls",
+    new_high_score: "New high score, enter your initials",
+    initial: "Initial",
+    high_scores: "High scores:",
+    won: "You won, score",
+    lost: "You lost, score",
+    round_done: "done",
+    player: "Player",
+    false_start: "false start",
+    wrong_button: "wrong button",
+    score: "Score",
+    tie: "Tie",
+    too_slow: "Too slow, nobody scores",
+    wins: "wins",
+};
+
+pub const CZECH: Messages = Messages {
+    scores_cleared: "Tabulka nejlepsich vymazana",
+    choose_level: "Stiskni tlacitko: cervena lehka, zelena normalni, modra tezka, zluta adaptivni",
+    simon_start: "Simon rika, stiskni cokoliv pro start",
+    duel_players: "Souboj: hrac 1 cervena a zelena, hrac 2 modra a zluta",
+    duel_cues: "Fialova znamena cervenou nebo modrou, zluta zelenou nebo zlutou. Stiskni pro start",
+    level: "Obtiznost",
+    levels: ["lehka", "normalni", "tezka", "adaptivni"],
+    foul: "Faul",
+    fouls: ["drzene tlacitko", "predcasny start", "mackani vseho"],
+    round: "Kolo",
+    hit: "trefa",
+    miss: "vedle",
+    reaction: "Reakce",
+    mean: "prumer",
+    best: "nejlepsi",
+    worst: "nejhorsi",
+    std_dev: "odchylka",
+    all_fouls: "Fauly",
+    new_high_score: "Novy rekord, zadej inicialy",
+    initial: "Inicial",
+    high_scores: "Nejlepsi:",
+    won: "vyhra, skore",
+    lost: "prohra, skore",
+    round_done: "hotovo",
+    player: "Hrac",
+    false_start: "predcasny start",
+    wrong_button: "spatne tlacitko",
+    score: "Skore",
+    tie: "Remiza",
+    too_slow: "Moc pomalu, nikdo neboduje",
+    wins: "vyhrava",
+};
+
+#[cfg(not(feature = "czech"))]
+pub const TEXT: &Messages = &ENGLISH;
+#[cfg(feature = "czech")]
+pub const TEXT: &Messages = &CZECH;
@@ -0,0 +1,287 @@
+//! Rules of the reaction game, free of any hardware.
+//!
+//! [`Game`] is fed the time and the held buttons and answers with
+//! [`Action`]s for the LED, the buzzer and the serial line. A [`Board`] does
+//! the feeding and carries the actions out, on the Arduino as well as in the
+//! scripted tests on the computer.
+
+use crate::common::color::Rgb;
+use crate::common::random::Rng;
+use crate::common::stats::Stats;
+use crate::difficulty::{Level, Pacing};
+
+/// Between the end of a round and the start of the next one, in µs.
+pub const PAUSE: u32 = 1_000_000;
+
+/// Presses of different buttons this close together are mashing, in µs.
+pub const MASH_WINDOW: u32 = 50_000;
+
+/// Edges this soon after the previous one are contact bounce, in µs.
+pub const DEBOUNCE: u32 = 20_000;
+
+/// Bits of the held buttons, in the order of [`Color::BUTTONS`].
+pub const ALL_BUTTONS: u8 = 0b1111;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Color {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    // Nothing may be pressed while it is shown.
+    Decoy,
+}
+
+impl Color {
+    pub const BUTTONS: [Color; 4] = [Color::Red, Color::Green, Color::Blue, Color::Yellow];
+
+    pub fn rgb(&self) -> Rgb {
+        match self {
+            Color::Red => Rgb::RED,
+            Color::Green => Rgb::GREEN,
+            Color::Blue => Rgb::BLUE,
+            Color::Yellow => Rgb::YELLOW,
+            Color::Decoy => Rgb::WHITE,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Tone {
+    Success,
+    Failure,
+}
+
+/// Ways of cheating, each loses the round and costs a point of the score.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Foul {
+    /// A button was already held when the color lit up.
+    PrePressed,
+    /// Pressed between rounds.
+    FalseStart,
+    /// Pressed more than one button within [`MASH_WINDOW`].
+    Mashing,
+}
+
+impl Foul {
+    /// Name in the event stream.
+    pub fn name(self) -> &'static str {
+        match self {
+            Foul::PrePressed => "pre_pressed",
+            Foul::FalseStart => "false_start",
+            Foul::Mashing => "mashing",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Action {
+    Show(Color),
+    Clear,
+    Play(Tone),
+    /// `count` is how many fouls of the kind there were this game.
+    Foul { foul: Foul, count: u8 },
+    /// `number` counts from 1, `reaction` is in µs and only there for
+    /// correct presses.
+    Round { number: u8, correct: bool, reaction: Option<u32> },
+    GameOver { score: u8, rounds: u8 },
+}
+
+/// What the game needs from the hardware.
+pub trait Board {
+    /// Free running microsecond clock, may wrap.
+    fn micros(&mut self) -> u32;
+    /// Held buttons, bit 0 red, 1 green, 2 blue and 3 yellow.
+    fn buttons(&mut self) -> u8;
+    fn perform(&mut self, action: Action);
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Ready,
+    Showing {
+        color: Color,
+        shown: u32,
+        display: u32,
+        // Button and reaction time of the first press, judged once no other
+        // button follows within the mashing window.
+        first: Option<(u8, u32)>,
+    },
+    Pause { since: u32 },
+    Over,
+}
+
+pub struct Game {
+    rng: Rng,
+    pacing: Pacing,
+    rounds: u8,
+    round: u8,
+    wins: u8,
+    fouls: [u8; 3],
+    reactions: Stats,
+    held: u8,
+    // When the held buttons last changed.
+    changed: u32,
+    state: State,
+}
+
+impl Game {
+    pub fn new(level: Level, rng: Rng) -> Self {
+        let pacing = Pacing::new(level);
+        Game {
+            rng,
+            rounds: pacing.settings().rounds,
+            pacing,
+            round: 0,
+            wins: 0,
+            fouls: [0; 3],
+            reactions: Stats::new(),
+            held: 0,
+            changed: 0,
+            state: State::Ready,
+        }
+    }
+
+    pub fn rounds(&self) -> u8 {
+        self.rounds
+    }
+
+    /// Correctly answered rounds.
+    pub fn wins(&self) -> u8 {
+        self.wins
+    }
+
+    pub fn fouls(&self, foul: Foul) -> u8 {
+        self.fouls[foul as usize]
+    }
+
+    /// Wins less a point for every foul.
+    pub fn score(&self) -> u8 {
+        self.wins.saturating_sub(self.fouls.iter().fold(0, |sum, &count| sum.saturating_add(count)))
+    }
+
+    pub fn is_won(&self) -> bool {
+        self.score() > self.rounds / 2
+    }
+
+    /// Reaction times of the correct presses.
+    pub fn reactions(&self) -> &Stats {
+        &self.reactions
+    }
+
+    pub fn is_over(&self) -> bool {
+        matches!(self.state, State::Over)
+    }
+
+    /// Advances the game to `now` (µs) with `buttons` held.
+    pub fn step(&mut self, now: u32, buttons: u8, mut act: impl FnMut(Action)) {
+        let buttons = buttons & ALL_BUTTONS;
+        let pressed = buttons & !self.held;
+        let settled = now.wrapping_sub(self.changed) >= DEBOUNCE;
+        if buttons != self.held {
+            self.changed = now;
+        }
+        self.held = buttons;
+
+        match self.state {
+            State::Ready => self.start_round(now, buttons, &mut act),
+            // A button let go at the end of the round bounces in the pause.
+            State::Pause { .. } if pressed != 0 && settled => {
+                self.foul(Foul::FalseStart, &mut act);
+                self.state = State::Pause { since: now };
+            }
+            State::Pause { since } if now.wrapping_sub(since) >= PAUSE => {
+                if self.round == self.rounds {
+                    self.state = State::Over;
+                    act(Action::GameOver { score: self.score(), rounds: self.rounds });
+                } else {
+                    self.start_round(now, buttons, &mut act);
+                }
+            }
+            State::Showing { color, shown, display, first } => {
+                let elapsed = now.wrapping_sub(shown);
+                match first {
+                    Some((button, _)) if pressed & !button != 0 => {
+                        self.foul(Foul::Mashing, &mut act);
+                        self.finish_round(now, color, false, None, &mut act);
+                    }
+                    None if pressed.count_ones() > 1 => {
+                        self.foul(Foul::Mashing, &mut act);
+                        self.finish_round(now, color, false, None, &mut act);
+                    }
+                    None if pressed != 0 => {
+                        self.state = State::Showing { color, shown, display, first: Some((pressed, elapsed)) };
+                    }
+                    Some((button, reaction)) if elapsed.wrapping_sub(reaction) >= MASH_WINDOW => {
+                        let correct = Color::BUTTONS[button.trailing_zeros() as usize] == color;
+                        self.finish_round(now, color, correct, Some(reaction), &mut act);
+                    }
+                    // Waiting it out is the right answer to a decoy.
+                    None if elapsed >= display => self.finish_round(now, color, color == Color::Decoy, None, &mut act),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start_round(&mut self, now: u32, buttons: u8, act: &mut impl FnMut(Action)) {
+        let color = if (self.rng.below(100) as u8) < self.pacing.settings().decoy_percent {
+            Color::Decoy
+        } else {
+            Color::BUTTONS[self.rng.below(4) as usize]
+        };
+
+        let (min_display, max_display) = self.pacing.window();
+        let display = self.rng.range(min_display, max_display) as u32 * 1000;
+
+        self.round += 1;
+        act(Action::Show(color));
+
+        if buttons != 0 {
+            self.foul(Foul::PrePressed, act);
+            self.finish_round(now, color, false, None, act);
+        } else {
+            self.state = State::Showing { color, shown: now, display, first: None };
+        }
+    }
+
+    fn foul(&mut self, foul: Foul, act: &mut impl FnMut(Action)) {
+        let count = &mut self.fouls[foul as usize];
+        *count = count.saturating_add(1);
+        act(Action::Foul { foul, count: *count });
+    }
+
+    fn finish_round(&mut self, now: u32, color: Color, correct: bool, reaction: Option<u32>, act: &mut impl FnMut(Action)) {
+        act(Action::Clear);
+
+        let reaction = if color == Color::Decoy {
+            None
+        } else {
+            self.pacing.record(correct);
+            reaction.filter(|_| correct)
+        };
+
+        if correct {
+            self.wins += 1;
+            if let Some(reaction) = reaction {
+                self.reactions.add(reaction);
+            }
+            act(Action::Play(Tone::Success));
+        } else {
+            act(Action::Play(Tone::Failure));
+        }
+        act(Action::Round { number: self.round, correct, reaction });
+
+        self.state = State::Pause { since: now };
+    }
+}
+
+/// Plays a whole game on `board`.
+pub fn run(game: &mut Game, board: &mut impl Board) {
+    while !game.is_over() {
+        let now = board.micros();
+        let buttons = board.buttons();
+        game.step(now, buttons, |action| board.perform(action));
+    }
+}
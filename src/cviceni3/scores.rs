@@ -0,0 +1,112 @@
+//! Best results of the reaction game, kept in the EEPROM.
+//!
+//! Levels play different numbers of rounds, so each one is ranked on its
+//! own. More correct rounds rank higher, a faster best reaction breaks ties.
+
+use crate::difficulty::Level;
+
+/// Places kept for each level.
+pub const PER_LEVEL: usize = 5;
+pub const TABLE_LEN: usize = Level::COUNT * PER_LEVEL;
+const ENTRY_SIZE: usize = 9;
+pub const SCORES_SIZE: usize = 1 + TABLE_LEN * ENTRY_SIZE;
+
+/// Stands in for the best reaction of a game without a correct press.
+pub const NO_REACTION: u32 = u32::MAX;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub level: Level,
+    pub score: u8,
+    /// Microseconds.
+    pub best_reaction: u32,
+    pub initials: [u8; 3],
+}
+
+impl Entry {
+    fn beats(&self, other: &Entry) -> bool {
+        self.score > other.score || (self.score == other.score && self.best_reaction < other.best_reaction)
+    }
+
+    pub fn initials(&self) -> &str {
+        core::str::from_utf8(&self.initials).unwrap_or("???")
+    }
+}
+
+/// Entries of all levels, grouped by level in the order of `Level`.
+pub struct Table {
+    entries: [Entry; TABLE_LEN],
+    len: usize,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        let empty = Entry { level: Level::Easy, score: 0, best_reaction: NO_REACTION, initials: *b"---" };
+        Table { entries: [empty; TABLE_LEN], len: 0 }
+    }
+
+    /// Ranking of `level`, best first.
+    pub fn entries(&self, level: Level) -> &[Entry] {
+        let (start, count) = self.span(level);
+        &self.entries[start..start + count]
+    }
+
+    /// Place a game of `level` with this result would take, `None` if it
+    /// does not make it into the table. Games without a correct round never
+    /// do.
+    pub fn rank(&self, level: Level, score: u8, best_reaction: u32) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        let entry = Entry { level, score, best_reaction, initials: [0; 3] };
+        let ranking = self.entries(level);
+        let place = ranking.iter().position(|other| entry.beats(other)).unwrap_or(ranking.len());
+        (place < PER_LEVEL).then_some(place)
+    }
+
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let place = self.rank(entry.level, entry.score, entry.best_reaction)?;
+        let (start, count) = self.span(entry.level);
+        // A full level drops its last entry, otherwise the levels after it
+        // move up.
+        let end = if count == PER_LEVEL { start + count - 1 } else { self.len };
+        self.entries.copy_within(start + place..end, start + place + 1);
+        self.entries[start + place] = entry;
+        if count < PER_LEVEL {
+            self.len += 1;
+        }
+        Some(place)
+    }
+
+    /// Where the entries of `level` begin and how many there are.
+    fn span(&self, level: Level) -> (usize, usize) {
+        let entries = &self.entries[..self.len];
+        let start = entries.iter().take_while(|entry| (entry.level as u8) < level as u8).count();
+        let count = entries[start..].iter().take_while(|entry| entry.level == level).count();
+        (start, count)
+    }
+
+    pub fn to_bytes(&self) -> [u8; SCORES_SIZE] {
+        let mut bytes = [0u8; SCORES_SIZE];
+        bytes[0] = self.len as u8;
+        for (entry, chunk) in self.entries.iter().zip(bytes[1..].chunks_exact_mut(ENTRY_SIZE)) {
+            chunk[0] = entry.level as u8;
+            chunk[1] = entry.score;
+            chunk[2..6].copy_from_slice(&entry.best_reaction.to_le_bytes());
+            chunk[6..9].copy_from_slice(&entry.initials);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; SCORES_SIZE]) -> Self {
+        let mut table = Table::new();
+        table.len = (bytes[0] as usize).min(TABLE_LEN);
+        for (entry, chunk) in table.entries.iter_mut().zip(bytes[1..].chunks_exact(ENTRY_SIZE)) {
+            entry.level = Level::from_index(chunk[0]);
+            entry.score = chunk[1];
+            entry.best_reaction = u32::from_le_bytes([chunk[2], chunk[3], chunk[4], chunk[5]]);
+            entry.initials.copy_from_slice(&chunk[6..9]);
+        }
+        table
+    }
+}
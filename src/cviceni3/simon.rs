@@ -0,0 +1,108 @@
+//! Simon says: repeat a sequence of colors that grows by one every round.
+//!
+//! Only the rules live here, colors are numbered 0 to 3 and the program
+//! decides how to show them and when the player took too long.
+
+pub const MAX_LENGTH: usize = 32;
+
+/// Tone of each color in Hz, the ones of the original game.
+pub const TONES: [u16; 4] = [310, 415, 209, 252];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    /// Waiting for the next color to be added.
+    Idle,
+    /// The program is playing the sequence.
+    Showing,
+    /// The player is repeating it, `position` colors are done.
+    Listening { position: usize },
+    Lost,
+    /// The whole [`MAX_LENGTH`] sequence was repeated.
+    Won,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// Correct, more colors to go.
+    Next,
+    /// Correct and the whole sequence is done.
+    RoundComplete,
+    Wrong,
+    /// The press came when no input was expected.
+    Ignored,
+}
+
+pub struct Simon {
+    sequence: [u8; MAX_LENGTH],
+    len: usize,
+    phase: Phase,
+}
+
+impl Simon {
+    pub fn new() -> Self {
+        Simon { sequence: [0; MAX_LENGTH], len: 0, phase: Phase::Idle }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The colors to show or repeat.
+    pub fn sequence(&self) -> &[u8] {
+        &self.sequence[..self.len]
+    }
+
+    /// Fully repeated rounds.
+    pub fn score(&self) -> usize {
+        match self.phase {
+            Phase::Idle | Phase::Won => self.len,
+            _ => self.len.saturating_sub(1),
+        }
+    }
+
+    /// Starts the next round with `color` appended. When the sequence is
+    /// already as long as it gets, the game is won instead.
+    pub fn next_round(&mut self, color: u8) -> Phase {
+        if self.phase == Phase::Idle {
+            if self.len == MAX_LENGTH {
+                self.phase = Phase::Won;
+            } else {
+                self.sequence[self.len] = color % 4;
+                self.len += 1;
+                self.phase = Phase::Showing;
+            }
+        }
+        self.phase
+    }
+
+    /// The sequence was shown, the player's turn.
+    pub fn shown(&mut self) {
+        if self.phase == Phase::Showing {
+            self.phase = Phase::Listening { position: 0 };
+        }
+    }
+
+    pub fn press(&mut self, color: u8) -> Outcome {
+        let Phase::Listening { position } = self.phase else {
+            return Outcome::Ignored;
+        };
+
+        if self.sequence[position] != color {
+            self.phase = Phase::Lost;
+            Outcome::Wrong
+        } else if position + 1 == self.len {
+            self.phase = Phase::Idle;
+            Outcome::RoundComplete
+        } else {
+            self.phase = Phase::Listening { position: position + 1 };
+            Outcome::Next
+        }
+    }
+
+    /// The player did not press anything in time.
+    pub fn timeout(&mut self) {
+        if let Phase::Listening { .. } = self.phase {
+            self.phase = Phase::Lost;
+        }
+    }
+}
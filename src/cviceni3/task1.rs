@@ -1,9 +1,27 @@
 #![no_std]
 #![no_main]
+#![feature(abi_avr_interrupt)]
 
+use arduino_hal::eeprom::Eeprom;
+use arduino_hal::prelude::*;
 use arduino_hal::simple_pwm::{IntoPwmPin, Prescaler, Timer1Pwm, Timer2Pwm};
 use panic_halt as _;
 
+#[path = "../common/mod.rs"]
+mod common;
+
+#[path = "../common/millis.rs"]
+mod millis;
+
+use common::analog::AnalogFilter;
+use common::color::{hsv, Rgb};
+use common::joystick::{Calibration, Calibrator, Joystick, FULL_SCALE};
+use common::rgb_led::RgbLed;
+use common::telemetry::{Encoder, Record};
+
+const CALIBRATION_ADDR: u16 = 0;
+const DEAD_ZONE: u16 = 80;
+
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = arduino_hal::Peripherals::take().unwrap();
@@ -11,6 +29,11 @@ fn main() -> ! {
 
     let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
 
+    let mut eep = Eeprom::new(dp.EEPROM);
+
+    millis::init(dp.TC0);
+    unsafe { avr_device::interrupt::enable() };
+
     let timer2 = Timer2Pwm::new(dp.TC2, Prescaler::Prescale64);
     let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale64);
 
@@ -19,52 +42,143 @@ fn main() -> ! {
     let mut led_b = pins.d10.into_output().into_pwm(&timer1);
     let mut led_r = pins.d9.into_output().into_pwm(&timer1);
 
+    // Hold the joystick button while resetting to calibrate again, press it
+    // later to switch between the axis and the color wheel mode.
+    let button = pins.d2.into_pull_up_input();
+
     let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
 
-    let x_axis = pins.a0.into_analog_input(&mut adc);
-    let y_axis = pins.a1.into_analog_input(&mut adc);
+    let x_axis = pins.a0.into_analog_input(&mut adc).into_channel();
+    let y_axis = pins.a1.into_analog_input(&mut adc).into_channel();
+    let mut joystick = Joystick::new(x_axis, y_axis, Calibration::DEFAULT);
+
+    let potenciometer = pins.a2.into_analog_input(&mut adc);
+    let mut value_knob = AnalogFilter::new().with_median(true);
 
     led_r.enable();
     led_g.enable();
     led_b.enable();
 
-    let max_duty_r = led_r.get_max_duty();
-    let max_duty_g = led_g.get_max_duty();
-    let max_duty_b = led_b.get_max_duty();
+    let mut led = RgbLed::pwm(led_r, led_g, led_b);
+
+    let stored = Calibration::load(&mut eep, CALIBRATION_ADDR);
+
+    let calibration = match stored {
+        Some(calibration) if button.is_high() => calibration,
+        _ => {
+            let mut calibrator = Calibrator::new();
+
+            ufmt::uwriteln!(&mut serial, "Calibrating, leave the joystick at rest").unwrap();
+            led.set(Rgb::new(0, 0, 255));
+            for _ in 0..100 {
+                let (x_value, y_value) = joystick.read_raw(&mut adc);
+                calibrator.rest(x_value, y_value);
+                arduino_hal::delay_ms(10);
+            }
+
+            ufmt::uwriteln!(&mut serial, "Now move it around all edges for 5 s").unwrap();
+            led.set(Rgb::new(255, 0, 0));
+            for _ in 0..500 {
+                let (x_value, y_value) = joystick.read_raw(&mut adc);
+                calibrator.sweep(x_value, y_value);
+                arduino_hal::delay_ms(10);
+            }
+            led.set(Rgb::new(0, 0, 0));
+
+            match calibrator.finish(DEAD_ZONE) {
+                Some(calibration) => {
+                    calibration.store(&mut eep, CALIBRATION_ADDR);
+                    calibration
+                }
+                None => {
+                    ufmt::uwriteln!(&mut serial, "Calibration failed, using defaults").unwrap();
+                    Calibration::DEFAULT
+                }
+            }
+        }
+    };
+    joystick.set_calibration(calibration);
+
+    ufmt::uwriteln!(
+        &mut serial,
+        "X: {}..{}..{}, Y: {}..{}..{}",
+        calibration.x.min,
+        calibration.x.center,
+        calibration.x.max,
+        calibration.y.min,
+        calibration.y.center,
+        calibration.y.max
+    )
+    .unwrap();
+
+    enum Mode {
+        // Each half of an axis drives one channel.
+        Axes,
+        // Angle is hue, deflection saturation and the potentiometer value.
+        Wheel,
+    }
+
+    let mut mode = Mode::Axes;
+    let mut button_was_pressed = button.is_low();
 
-    const X_CENTER: u16 = 495;
-    const Y_CENTER: u16 = 506;
+    // Sending `T` switches between text and binary telemetry frames.
+    let mut telemetry: Option<Encoder> = None;
 
     loop {
         //led_r.toggle();
-        let x_value = x_axis.analog_read(&mut adc);
-        let y_value = y_axis.analog_read(&mut adc);
-
-        ufmt::uwriteln!(&mut serial, "X: {}, Y: {}", x_value, y_value).unwrap();
-
-        let red_intensity = if x_value > X_CENTER {
-            ((x_value - X_CENTER) * 255 / (1024 - X_CENTER)) as u8
-        } else {
-            0u8
+        if let Ok(b'T' | b't') = serial.read() {
+            telemetry = match telemetry {
+                Some(_) => None,
+                None => Some(Encoder::new()),
+            };
+        }
+
+        let (reading, _) = joystick.poll(&mut adc);
+
+        match telemetry.as_mut() {
+            Some(encoder) => {
+                let record = Record::Joystick { x: reading.x_raw, y: reading.y_raw };
+                encoder.send(millis::millis(), &record, |byte| serial.write_byte(byte));
+            }
+            None => ufmt::uwriteln!(&mut serial, "X: {}, Y: {}", reading.x_raw, reading.y_raw).unwrap(),
+        }
+
+        let button_pressed = button.is_low();
+        if button_pressed && !button_was_pressed {
+            let (next, name) = match mode {
+                Mode::Axes => (Mode::Wheel, "color wheel"),
+                Mode::Wheel => (Mode::Axes, "axes"),
+            };
+            mode = next;
+            if telemetry.is_none() {
+                ufmt::uwriteln!(&mut serial, "Mode: {}", name).unwrap();
+            }
+        }
+        button_was_pressed = button_pressed;
+
+        let color = match mode {
+            Mode::Axes => Rgb::new(
+                (reading.x.max(0) * 255 / FULL_SCALE) as u8,
+                ((-reading.x).max(0) * 255 / FULL_SCALE) as u8,
+                ((-reading.y).max(0) * 255 / FULL_SCALE) as u8,
+            ),
+            Mode::Wheel => {
+                let raw = potenciometer.analog_read(&mut adc);
+                if let Some(encoder) = telemetry.as_mut() {
+                    let record = Record::Analog { channel: 2, value: raw };
+                    encoder.send(millis::millis(), &record, |byte| serial.write_byte(byte));
+                }
+                let value = value_knob.update(raw) as u8;
+                let saturation = (reading.magnitude as u32 * 255 / FULL_SCALE as u32) as u8;
+                hsv(reading.angle, saturation, value)
+            }
         };
 
-        let green_intensity = if x_value < X_CENTER {
-            ((X_CENTER - x_value) * 255 / X_CENTER) as u8
-        } else {
-            0u8
-        };
-
-        let blue_intensity = if y_value < Y_CENTER {
-            ((Y_CENTER - y_value) * 255 / Y_CENTER) as u8
-        } else {
-            0u8
-        } ;
-
-        ufmt::uwriteln!(&mut serial, "RGB: ({}, {}, {})", red_intensity, green_intensity, blue_intensity).unwrap();
+        if telemetry.is_none() {
+            ufmt::uwriteln!(&mut serial, "RGB: ({}, {}, {})", color.r, color.g, color.b).unwrap();
+        }
 
-        led_r.set_duty(red_intensity);
-        led_g.set_duty(green_intensity);
-        led_b.set_duty(blue_intensity);
+        led.set(color);
 
         arduino_hal::delay_ms(10);
     }
@@ -0,0 +1,186 @@
+use std::error::Error;
+
+// Only the conversions are checked here.
+#[allow(dead_code)]
+#[path = "../common"]
+mod common {
+    pub mod color;
+    pub mod fixed;
+    pub mod joystick;
+    pub mod report;
+}
+
+use common::color::{self, Rgb};
+use common::joystick::{AnalogChannel, Axis, Calibration, Direction, Joystick, FULL_SCALE};
+use common::report::Report;
+
+const USAGE: &str = "Usage:
+  task1_computer hsv
+  task1_computer joystick";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("hsv") if args.len() == 2 => {
+            if !check_hsv() {
+                std::process::exit(1);
+            }
+        }
+        Some("joystick") if args.len() == 2 => {
+            if !check_joystick() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// HSV to RGB in floating point, each channel 0.0..=255.0.
+fn hsv_reference(hue: u16, saturation: u8, value: u8) -> [f64; 3] {
+    let h = (hue % 360) as f64 / 60.0;
+    let s = saturation as f64 / 255.0;
+    let v = value as f64;
+    let chroma = v * s;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = v - chroma;
+    let [r, g, b] = match h as u16 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
+/// Sweeps the integer HSV conversion against floating point, returns whether
+/// it stays as close as its documentation says.
+fn check_hsv() -> bool {
+    let mut report = Report::new();
+
+    let mut worst = 0.0f64;
+    let mut off_by_one = 0;
+    let mut only_halves = true;
+    for hue in 0..360 {
+        for saturation in 0..=255 {
+            for value in 0..=255 {
+                let Rgb { r, g, b } = color::hsv(hue, saturation, value);
+                for (channel, exact) in [r, g, b].into_iter().zip(hsv_reference(hue, saturation, value)) {
+                    let error = (channel as f64 - exact).abs();
+                    worst = worst.max(error);
+                    if channel as f64 != exact.round() {
+                        off_by_one += 1;
+                        only_halves &= (exact.fract() - 0.5).abs() < 1e-9;
+                    }
+                }
+            }
+        }
+    }
+    report.value("hsv worst error", worst, worst <= 0.5 + 1e-9);
+    report.value("hsv differing roundings", off_by_one as f64, only_halves);
+
+    let primaries = color::hsv(0, 255, 255) == Rgb::RED
+        && color::hsv(120, 255, 255) == Rgb::GREEN
+        && color::hsv(240, 255, 255) == Rgb::BLUE
+        && color::hsv(60, 255, 255) == Rgb::YELLOW;
+    report.check("hsv primaries", primaries);
+    report.check("hsv hue wraps at 360", color::hsv(400, 200, 100) == color::hsv(40, 200, 100));
+    report.check("hsv without saturation is gray", (0..360).all(|hue| color::hsv(hue, 0, 77) == Rgb::new(77, 77, 77)));
+
+    report.passed()
+}
+
+/// Stands in for the ADC, holding what the two axes read.
+struct FakeAdc {
+    x: u16,
+    y: u16,
+}
+
+impl FakeAdc {
+    /// Puts the stick of an ideal joystick at `angle` degrees and `radius`
+    /// (in [`FULL_SCALE`] units) from the center.
+    fn tilt(&mut self, angle: f64, radius: f64) {
+        let raw = |position: f64| (512.0 + position / FULL_SCALE as f64 * 511.0).round() as u16;
+        self.x = raw(radius * angle.to_radians().cos());
+        self.y = raw(radius * angle.to_radians().sin());
+    }
+}
+
+enum Pin {
+    X,
+    Y,
+}
+
+impl AnalogChannel<FakeAdc> for Pin {
+    fn read(&self, adc: &mut FakeAdc) -> u16 {
+        match self {
+            Pin::X => adc.x,
+            Pin::Y => adc.y,
+        }
+    }
+}
+
+/// Moves a joystick on a fake ADC around, returns whether its readings and
+/// direction events came out as expected.
+fn check_joystick() -> bool {
+    let mut report = Report::new();
+
+    let axis = Axis { min: 100, center: 400, max: 900 };
+    let ends = axis.normalize(900) == FULL_SCALE && axis.normalize(100) == -FULL_SCALE && axis.normalize(400) == 0;
+    let halves = axis.normalize(650) == FULL_SCALE / 2 && axis.normalize(250) == -FULL_SCALE / 2;
+    let clamped = axis.normalize(1023) == FULL_SCALE && axis.normalize(0) == -FULL_SCALE;
+    report.check("axis normalization", ends && halves && clamped);
+
+    let mut adc = FakeAdc { x: 512, y: 512 };
+    let mut joystick = Joystick::new(Pin::X, Pin::Y, Calibration::DEFAULT);
+    let dead_zone = Calibration::DEFAULT.dead_zone as f64;
+
+    let inside = (0..360).step_by(15).all(|angle| {
+        adc.tilt(angle as f64, dead_zone - 10.0);
+        let (reading, _) = joystick.poll(&mut adc);
+        reading.x == 0 && reading.y == 0 && reading.magnitude == 0
+    });
+    report.check("dead zone reads as the center", inside);
+    adc.tilt(0.0, dead_zone + 20.0);
+    let (reading, _) = joystick.poll(&mut adc);
+    report.value("just outside the dead zone", reading.magnitude as f64, (0..=40).contains(&reading.magnitude));
+    let full = (0..360).step_by(15).all(|angle| {
+        adc.tilt(angle as f64, FULL_SCALE as f64);
+        let (reading, _) = joystick.poll(&mut adc);
+        let error = (reading.angle as i32 - angle).rem_euclid(360);
+        reading.magnitude as i32 >= FULL_SCALE - 5 && error.min(360 - error) <= 1
+    });
+    report.check("full deflection angle and size", full);
+
+    // Every poll from here on samples the stick where `tilt` put it.
+    let mut events = |joystick: &mut Joystick<Pin, Pin>, angle: f64, radius: f64, polls: usize| {
+        adc.tilt(angle, radius);
+        (0..polls).filter_map(|_| joystick.poll(&mut adc).1).collect::<Vec<_>>()
+    };
+    let mut joystick = Joystick::new(Pin::X, Pin::Y, Calibration::DEFAULT);
+
+    let first = events(&mut joystick, 0.0, 900.0, 2);
+    let third = events(&mut joystick, 0.0, 900.0, 1);
+    report.check("direction after three samples agree", first.is_empty() && third == [Direction::Right]);
+    let glitch = events(&mut joystick, 90.0, 900.0, 2);
+    let back = events(&mut joystick, 0.0, 900.0, 5);
+    report.check("short glitch is ignored", glitch.is_empty() && back.is_empty());
+    let near = events(&mut joystick, 28.0, 900.0, 5);
+    let past = events(&mut joystick, 35.0, 900.0, 5);
+    report.check("hysteresis past the sector border", near.is_empty() && past == [Direction::UpRight]);
+    let held = events(&mut joystick, 35.0, 500.0, 5);
+    let released = events(&mut joystick, 35.0, 250.0, 5);
+    report.check("center below the release level", held.is_empty() && released == [Direction::Center]);
+    let weak = events(&mut joystick, 180.0, 450.0, 5);
+    let strong = events(&mut joystick, 180.0, 700.0, 5);
+    report.check("leaves the center past engage level", weak.is_empty() && strong == [Direction::Left]);
+
+    report.passed()
+}
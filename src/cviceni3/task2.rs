@@ -1,9 +1,34 @@
 #![no_std]
 #![no_main]
+#![feature(abi_avr_interrupt)]
 
+use arduino_hal::eeprom::Eeprom;
 use arduino_hal::simple_pwm::{IntoPwmPin, Prescaler, Timer1Pwm, Timer2Pwm};
 use panic_halt as _;
 
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::analog::{AnalogFilter, Curve};
+use common::color::Rgb;
+use common::joystick::{Calibration, Direction, Joystick};
+use common::rgb_led::RgbLed;
+use common::tone;
+
+// Written by the calibration in task1, same board.
+const CALIBRATION_ADDR: u16 = 0;
+
+const BEEP_FREQUENCY: u16 = 2000;
+// A little longer than one pass of the loop, so holding the button beeps
+// without gaps.
+const BEEP_DURATION: u16 = 20;
+
+// Timers 1 and 2 drive the LED, the buzzer gets timer 0.
+#[avr_device::interrupt(atmega328p)]
+fn TIMER0_COMPA() {
+    tone::interrupt();
+}
+
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = arduino_hal::Peripherals::take().unwrap();
@@ -11,6 +36,8 @@ fn main() -> ! {
 
     let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
 
+    let mut eep = Eeprom::new(dp.EEPROM);
+
     let timer2 = Timer2Pwm::new(dp.TC2, Prescaler::Prescale64);
     let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale64);
 
@@ -20,22 +47,29 @@ fn main() -> ! {
     let mut led_r = pins.d9.into_output().into_pwm(&timer1);
 
 
-    let mut buzzer = pins.d7.into_output();
+    tone::init(dp.TC0, pins.d7.into_output().downgrade());
+    unsafe { avr_device::interrupt::enable() };
 
     let button = pins.d2.into_pull_up_input();
 
     let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
 
-    let x_axis = pins.a0.into_analog_input(&mut adc);
-    let y_axis = pins.a1.into_analog_input(&mut adc);
+    let x_axis = pins.a0.into_analog_input(&mut adc).into_channel();
+    let y_axis = pins.a1.into_analog_input(&mut adc).into_channel();
+    let calibration = Calibration::load(&mut eep, CALIBRATION_ADDR).unwrap_or(Calibration::DEFAULT);
+    let mut joystick = Joystick::new(x_axis, y_axis, calibration);
 
 
     let potenciometer = pins.a2.into_analog_input(&mut adc);
+    // The LED already corrects for perception, so the knob maps linearly.
+    let mut brightness_knob = AnalogFilter::new().with_median(true).with_curve(Curve::Linear);
 
     led_r.enable();
     led_g.enable();
     led_b.enable();
 
+    let mut led = RgbLed::pwm(led_r, led_g, led_b);
+
 
     enum Color {
         Red,
@@ -48,47 +82,31 @@ fn main() -> ! {
 
     loop {
 
-        let x_value = x_axis.analog_read(&mut adc);
-        let y_value = y_axis.analog_read(&mut adc);
+        let (reading, _) = joystick.poll(&mut adc);
 
         let potenciometer_value = potenciometer.analog_read(&mut adc);
-
-        //let potenciometer_value = potenciometer_value.saturating_sub(490).saturating_mul(5) as u8;
-
-        if x_value < 100 {
-            color = Color::Red;
-        } else if x_value > 900 {
-            color = Color::Blue;
-        } else if y_value < 100 {
-            color = Color::Green;
-        } else {
-            if button.is_low() {
-                buzzer.set_high();
-                arduino_hal::delay_ms(2);
-                buzzer.set_low();
+        let brightness = brightness_knob.update(potenciometer_value) as u8;
+
+        match joystick.direction() {
+            Direction::Left | Direction::UpLeft | Direction::DownLeft => color = Color::Red,
+            Direction::Right | Direction::UpRight | Direction::DownRight => color = Color::Blue,
+            Direction::Down => color = Color::Green,
+            Direction::Up | Direction::Center => {
+                if button.is_low() {
+                    tone::play(BEEP_FREQUENCY, BEEP_DURATION);
+                }
+                color = Color::None;
             }
-            color = Color::None;
         }
 
-        ufmt::uwriteln!(&mut serial, "X: {}, Y: {} P: {}", x_value, y_value, potenciometer_value).unwrap();
-
+        ufmt::uwriteln!(&mut serial, "X: {}, Y: {} P: {}", reading.x_raw, reading.y_raw, brightness_knob.value()).unwrap();
 
-        led_r.set_duty(if matches!(color, Color::Red) {
-            potenciometer_value as u8
-        } else {
-            0
-        });
-
-        led_g.set_duty(if matches!(color, Color::Green) {
-            potenciometer_value as u8
-        } else {
-            0
-        });
 
-        led_b.set_duty(if matches!(color, Color::Blue) {
-            potenciometer_value as u8
-        } else {
-            0
+        led.set(match color {
+            Color::Red => Rgb::new(brightness, 0, 0),
+            Color::Green => Rgb::new(0, brightness, 0),
+            Color::Blue => Rgb::new(0, 0, brightness),
+            Color::None => Rgb::new(0, 0, 0),
         });
 
         arduino_hal::delay_ms(10);
@@ -1,17 +1,181 @@
 #![no_std]
 #![no_main]
+#![feature(abi_avr_interrupt)]
 
+use arduino_hal::eeprom::Eeprom;
+use arduino_hal::hal::usart::Usart0;
+use arduino_hal::port::mode::{Input, Output, PullUp};
+use arduino_hal::port::Pin;
 use arduino_hal::prelude::*;
-use arduino_hal::{hal::port::{PB1, PB2, PB3, PD7}, port::mode::Output};
+use arduino_hal::DefaultClock;
+use avr_device::interrupt::Mutex;
+use core::cell::Cell;
 use panic_halt as _;
 
+#[path = "../common/mod.rs"]
+mod common;
+#[path = "../common/millis.rs"]
+mod millis;
+mod difficulty;
+mod duel;
+mod events;
+mod messages;
+mod reaction;
+mod scores;
+mod simon;
+
+use common::animation::Animator;
+use common::color::Rgb;
+use common::fixed;
+use common::melody;
+use common::persist;
+use common::random::{Entropy, Rng};
+use common::rgb_led::{Digital, RgbLed};
+use common::tone;
+use difficulty::Level;
+use duel::{Duel, PLAYER_COLORS};
+use events::{FOUL, GAME_END, GAME_START, ROUND};
+use messages::TEXT;
+use reaction::{Action, Board, Color, Foul, Game, Tone};
+use scores::{Entry, Table, NO_REACTION, SCORES_SIZE};
+use simon::{Outcome, Phase, Simon, TONES};
+
+const SEED_ADDR: u16 = 0;
+const SCORES_ADDR: u16 = SEED_ADDR + persist::record_size(4);
+
+const SUCCESS: &str = "success:d=16,o=6,b=180:c,e,g";
+const FAILURE: &str = "failure:d=8,o=4,b=120:g,4c";
+const VICTORY: &str = "victory:d=8,o=5,b=160:c6,c6,c6,4c6,4g#5,4a#5,c6,p,a#5,2c6";
+const DEFEAT: &str = "defeat:d=4,o=5,b=90:g,f#,f,2e";
+
+// Simon says timing, in ms.
+const SIMON_NOTE: u16 = 400;
+const SIMON_GAP: u16 = 150;
+const SIMON_TIMEOUT: u32 = 3000;
+
 #[derive(PartialEq, Clone, Copy)]
-enum Color {
-    Red,
-    Green,
-    Blue,
-    Yellow,
-    None,
+enum Mode {
+    Reaction,
+    Simon,
+    Duel,
+}
+
+type Led = RgbLed<Digital<Pin<Output>>, Digital<Pin<Output>>, Digital<Pin<Output>>>;
+type Buttons = [Pin<Input<PullUp>>; 4];
+type Serial = Usart0<DefaultClock>;
+
+/// Held buttons in the order of [`Color::BUTTONS`], one bit each.
+fn held(buttons: &Buttons) -> u8 {
+    buttons
+        .iter()
+        .enumerate()
+        .fold(0, |mask, (i, button)| if button.is_low() { mask | 1 << i } else { mask })
+}
+
+// When each button was first pressed since the main loop last looked, the
+// duel needs more precision than polling gives.
+static PRESSES: Mutex<Cell<[Option<u32>; 4]>> = Mutex::new(Cell::new([None; 4]));
+static HELD: Mutex<Cell<u8>> = Mutex::new(Cell::new(0));
+
+#[avr_device::interrupt(atmega328p)]
+#[allow(non_snake_case)]
+fn PCINT2() {
+    let now = millis::micros();
+    let pind = unsafe { (*avr_device::atmega328p::PORTD::ptr()).pind.read().bits() };
+    // D2 to D5, pressed is low.
+    let held = !pind >> 2 & 0b1111;
+
+    avr_device::interrupt::free(|cs| {
+        let pressed = held & !HELD.borrow(cs).replace(held);
+        let mut presses = PRESSES.borrow(cs).get();
+        for (i, press) in presses.iter_mut().enumerate() {
+            if pressed & 1 << i != 0 && press.is_none() {
+                *press = Some(now);
+            }
+        }
+        PRESSES.borrow(cs).set(presses);
+    });
+}
+
+fn take_presses() -> [Option<u32>; 4] {
+    avr_device::interrupt::free(|cs| PRESSES.borrow(cs).replace([None; 4]))
+}
+
+fn jingle(text: &str) {
+    if let Ok(notes) = melody::parse(text) {
+        tone::play_notes(notes);
+    }
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn TIMER2_COMPA() {
+    tone::interrupt();
+}
+
+/// Runs the reaction game on the real pins.
+struct Hardware<'a> {
+    led: &'a mut Led,
+    buttons: &'a Buttons,
+    serial: &'a mut Serial,
+    /// Whether to send the event lines as well.
+    events: bool,
+    // The foul that lost the current round, if any.
+    foul: Option<Foul>,
+}
+
+impl Board for Hardware<'_> {
+    fn micros(&mut self) -> u32 {
+        millis::micros()
+    }
+
+    fn buttons(&mut self) -> u8 {
+        held(self.buttons)
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Show(color) => self.led.set(color.rgb()),
+            Action::Clear => self.led.off(),
+            Action::Play(Tone::Success) => jingle(SUCCESS),
+            Action::Play(Tone::Failure) => jingle(FAILURE),
+            Action::Foul { foul, count } => {
+                ufmt::uwriteln!(&mut *self.serial, "{}: {} ({})", TEXT.foul, TEXT.fouls[foul as usize], count).unwrap();
+                if self.events {
+                    ufmt::uwriteln!(&mut *self.serial, "{} kind={} count={}", FOUL, foul.name(), count).unwrap();
+                }
+                // A false start only restarts the pause.
+                if foul != Foul::FalseStart {
+                    self.foul = Some(foul);
+                }
+            }
+            Action::Round { number, correct, reaction } => {
+                let result = if correct { TEXT.hit } else { TEXT.miss };
+                ufmt::uwriteln!(&mut *self.serial, "{} {}: {}", TEXT.round, number, result).unwrap();
+                if let Some(reaction) = reaction {
+                    ufmt::uwriteln!(&mut *self.serial, "{}: {} us", TEXT.reaction, reaction).unwrap();
+                }
+
+                let outcome = match self.foul.take() {
+                    Some(_) => events::Outcome::Foul,
+                    None if correct => events::Outcome::Hit,
+                    None => events::Outcome::Miss,
+                };
+                if self.events {
+                    ufmt::uwrite!(&mut *self.serial, "{} n={} result={}", ROUND, number, outcome.name()).unwrap();
+                    if let Some(reaction) = reaction {
+                        let mut buf = [0; fixed::MAX_LEN];
+                        ufmt::uwrite!(&mut *self.serial, " ms={}", fixed::format(reaction as i32, 3, &mut buf)).unwrap();
+                    }
+                    ufmt::uwriteln!(&mut *self.serial, "").unwrap();
+                }
+            }
+            Action::GameOver { score, rounds } => {
+                if self.events {
+                    ufmt::uwriteln!(&mut *self.serial, "{} score={} rounds={}", GAME_END, score, rounds).unwrap();
+                }
+            }
+        }
+    }
 }
 
 #[arduino_hal::entry]
@@ -21,165 +185,289 @@ fn main() -> ! {
 
     let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
 
-    let mut led_r = pins.d9.into_output();
-    let mut led_g = pins.d11.into_output();
-    let mut led_b = pins.d10.into_output();
-
-    let mut buzzer = pins.d7.into_output();
-
-    let button_red    = pins.d2.into_pull_up_input();
-    let button_green  = pins.d3.into_pull_up_input();
-    let button_blue   = pins.d4.into_pull_up_input();
-    let button_yellow = pins.d5.into_pull_up_input();
-
-    const GAME_ROUNDS: u8 = 5;
-    const MIN_DISPLAY_TIME: u16 = 800;
-    const MAX_DISPLAY_TIME: u16 = 1500;
-    const WIN_THRESHOLD: u8 = GAME_ROUNDS / 2 + 1;
-
-    fn set_led_color(
-        color: &Color,
-        led_r: &mut arduino_hal::port::Pin<Output, PB1>,
-        led_g: &mut arduino_hal::port::Pin<Output, PB3>,
-        led_b: &mut arduino_hal::port::Pin<Output, PB2>
-    ) {
-        match color {
-            Color::Red => {
-                led_r.set_high();
-                led_g.set_low();
-                led_b.set_low();
-            },
-            Color::Green => {
-                led_r.set_low();
-                led_g.set_high();
-                led_b.set_low();
-            },
-            Color::Blue => {
-                led_r.set_low();
-                led_g.set_low();
-                led_b.set_high();
-            },
-            Color::Yellow => {
-                led_r.set_high();
-                led_g.set_high();
-                led_b.set_low();
-            },
-            Color::None => {
-                led_r.set_low();
-                led_g.set_low();
-                led_b.set_low();
-            },
-        }
+    let mut led = RgbLed::digital(
+        pins.d9.into_output().downgrade(),
+        pins.d11.into_output().downgrade(),
+        pins.d10.into_output().downgrade(),
+    );
+    let mut animator = Animator::new();
+
+    millis::init(dp.TC0);
+    unsafe { avr_device::interrupt::enable() };
+
+    tone::init(dp.TC2, pins.d7.into_output().downgrade());
+
+    // Red, green, blue and yellow.
+    let buttons = [
+        pins.d2.into_pull_up_input().downgrade(),
+        pins.d3.into_pull_up_input().downgrade(),
+        pins.d4.into_pull_up_input().downgrade(),
+        pins.d5.into_pull_up_input().downgrade(),
+    ];
+
+    let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
+    // Nothing is connected here, the lowest bits are noise.
+    let floating = pins.a5.into_analog_input(&mut adc);
+
+    let mut eep = Eeprom::new(dp.EEPROM);
+
+    // Holding red and yellow while resetting clears the high scores.
+    if held(&buttons) == 0b1001 {
+        persist::erase(&mut eep, SCORES_ADDR, SCORES_SIZE);
+        ufmt::uwriteln!(&mut serial, "{}", TEXT.scores_cleared).unwrap();
+        while held(&buttons) != 0 {}
     }
+    // Holding blue while resetting plays Simon says instead, green the duel.
+    let mode = match held(&buttons) {
+        0b0100 => Mode::Simon,
+        0b0010 => Mode::Duel,
+        _ => Mode::Reaction,
+    };
+    while held(&buttons) != 0 {}
 
-    fn play_success_tone(buzzer: &mut arduino_hal::port::Pin<Output, PD7>) {
-        for _ in 0..100 {
-            buzzer.set_high();
-            arduino_hal::delay_us(500);
-            buzzer.set_low();
-            arduino_hal::delay_us(500);
-        }
+    let mut bytes = [0u8; SCORES_SIZE];
+    let mut scores = if persist::load(&mut eep, SCORES_ADDR, &mut bytes) {
+        Table::from_bytes(&bytes)
+    } else {
+        Table::new()
+    };
+
+    // The seed saved by the previous boot, the floating pin and the moment
+    // the player starts the game all go into the seed.
+    let mut entropy = Entropy::new();
+    let mut stored = [0u8; 4];
+    if persist::load(&mut eep, SEED_ADDR, &mut stored) {
+        entropy.add(u32::from_le_bytes(stored));
+    }
+    for _ in 0..32 {
+        entropy.add(floating.analog_read(&mut adc) as u32 ^ (millis::micros() << 16));
     }
 
-    fn play_failure_tone(buzzer: &mut arduino_hal::port::Pin<Output, PD7>) {
-        for _ in 0..150 {
-            buzzer.set_high();
-            arduino_hal::delay_us(2000);
-            buzzer.set_low();
-            arduino_hal::delay_us(2000);
+    match mode {
+        Mode::Reaction => ufmt::uwriteln!(&mut serial, "{}", TEXT.choose_level).unwrap(),
+        Mode::Simon => ufmt::uwriteln!(&mut serial, "{}", TEXT.simon_start).unwrap(),
+        Mode::Duel => {
+            ufmt::uwriteln!(&mut serial, "{}", TEXT.duel_players).unwrap();
+            ufmt::uwriteln!(&mut serial, "{}", TEXT.duel_cues).unwrap();
         }
     }
+    animator.breathe(Rgb::WHITE, 2000, millis::millis());
+    let mut start = 0;
+    // A logger on the other end asks for the event lines meanwhile.
+    let mut events = false;
+    while start == 0 {
+        led.set(animator.update(millis::millis()));
+        start = held(&buttons);
+        if let Ok(events::ENABLE) = serial.read() {
+            events = true;
+        }
+    }
+    entropy.add(millis::micros());
+    let level = Level::from_index(start.trailing_zeros() as u8);
+    animator.stop();
+    led.off();
+    while held(&buttons) != 0 {}
+    arduino_hal::delay_ms(500);
 
+    let mut rng = Rng::new(entropy.seed());
+    persist::store(&mut eep, SEED_ADDR, &rng.next_u32().to_le_bytes());
 
-    fn rand(seed: &mut u32) -> u32 {
-        *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
-        *seed
-    }
+    if mode == Mode::Simon {
+        let mut simon = Simon::new();
+        while simon.next_round(rng.below(4) as u8) == Phase::Showing {
+            arduino_hal::delay_ms(500);
+            for &color in simon.sequence() {
+                led.set(Color::BUTTONS[color as usize].rgb());
+                tone::play(TONES[color as usize], SIMON_NOTE);
+                while tone::is_playing() {}
+                led.off();
+                arduino_hal::delay_ms(SIMON_GAP);
+            }
+            simon.shown();
+
+            let mut last_press = millis::millis();
+            while let Phase::Listening { .. } = simon.phase() {
+                let pressed = held(&buttons);
+                if pressed == 0 {
+                    if millis::millis().wrapping_sub(last_press) > SIMON_TIMEOUT {
+                        simon.timeout();
+                    }
+                    continue;
+                }
 
-    let mut seed: u32 = 0xDEADBEEF;
+                let color = pressed.trailing_zeros() as u8;
+                led.set(Color::BUTTONS[color as usize].rgb());
+                tone::play(TONES[color as usize], u16::MAX);
+                arduino_hal::delay_ms(20);
+                while held(&buttons) != 0 {}
+                tone::stop();
+                led.off();
+                arduino_hal::delay_ms(20);
+                last_press = millis::millis();
 
-    let mut wins: u8 = 0;
-    let mut cheating: u8 = 0;
+                if simon.press(color) == Outcome::RoundComplete {
+                    ufmt::uwriteln!(&mut serial, "{} {}: {}", TEXT.round, simon.score(), TEXT.round_done).unwrap();
+                }
+            }
+        }
 
-    for round in 0..GAME_ROUNDS {
-        let r = rand(&mut seed);
-        let color = match r % 4 {
-            0 => Color::Red,
-            1 => Color::Green,
-            2 => Color::Blue,
-            3 => Color::Yellow,
-            _ => Color::None,
-        };
+        if simon.phase() == Phase::Won {
+            jingle(VICTORY);
+            ufmt::uwriteln!(&mut serial, "{} {}", TEXT.won, simon.score()).unwrap();
+            animator.blink(Rgb::GREEN, 300, 300, 3, millis::millis());
+        } else {
+            jingle(DEFEAT);
+            ufmt::uwriteln!(&mut serial, "{} {}", TEXT.lost, simon.score()).unwrap();
+            animator.blink(Rgb::RED, 300, 300, 3, millis::millis());
+        }
 
-        set_led_color(&color, &mut led_r, &mut led_g, &mut led_b);
+        loop {
+            led.set(animator.update(millis::millis()));
+        }
+    }
 
-        let delay_range = (MAX_DISPLAY_TIME - MIN_DISPLAY_TIME + 1) as u32;
-        let display_time = MIN_DISPLAY_TIME + (rand(&mut seed) % delay_range) as u16;
+    if mode == Mode::Duel {
+        // Pin change interrupt for D2 to D5.
+        dp.EXINT.pcicr.write(|w| unsafe { w.bits(0b100) });
+        dp.EXINT.pcmsk2.write(|w| w.bits(0b0011_1100));
 
-        let mut correct_pressed = false;
-        let mut elapsed: u16 = 0;
-        while elapsed < display_time {
-            if (button_red.is_low() &&  button_green.is_low() && button_blue.is_low() && button_yellow.is_low()) {
-                correct_pressed = true;
-                cheating += 1;
-                ufmt::uwriteln!(&mut serial, "p {}", cheating).unwrap();
-                break;
-            } else if button_red.is_low() {
-                if color == Color::Red {
-                    correct_pressed = true;
+        let mut duel = Duel::new(rng);
+        let mut winner = 0;
+        while !duel.is_over() {
+            let now = millis::micros();
+            duel.step(now, take_presses(), |action| match action {
+                duel::Action::Show(cue) => led.set(cue.rgb()),
+                duel::Action::Clear => led.off(),
+                duel::Action::FalseStart { player } => {
+                    jingle(FAILURE);
+                    ufmt::uwriteln!(&mut serial, "{} {}: {}", TEXT.player, player + 1, TEXT.false_start).unwrap();
                 }
-                break;
-            } else if button_green.is_low() {
-                if color == Color::Green {
-                    correct_pressed = true;
+                duel::Action::Wrong { player } => {
+                    ufmt::uwriteln!(&mut serial, "{} {}: {}", TEXT.player, player + 1, TEXT.wrong_button).unwrap();
                 }
-                break;
-            } else if button_blue.is_low() {
-                if color == Color::Blue {
-                    correct_pressed = true;
+                duel::Action::Point { player, reaction, scores } => {
+                    jingle(SUCCESS);
+                    if reaction > 0 {
+                        ufmt::uwriteln!(&mut serial, "{} {}: {} us", TEXT.player, player + 1, reaction).unwrap();
+                    }
+                    ufmt::uwriteln!(&mut serial, "{}: {} : {}", TEXT.score, scores[0], scores[1]).unwrap();
+                    // Blink the points of whoever scored in their color.
+                    for _ in 0..scores[player as usize] {
+                        led.set(PLAYER_COLORS[player as usize]);
+                        arduino_hal::delay_ms(150);
+                        led.off();
+                        arduino_hal::delay_ms(150);
+                    }
                 }
-                break;
-            } else if button_yellow.is_low() {
-                if color == Color::Yellow {
-                    correct_pressed = true;
+                duel::Action::Tie => ufmt::uwriteln!(&mut serial, "{}", TEXT.tie).unwrap(),
+                duel::Action::Timeout => ufmt::uwriteln!(&mut serial, "{}", TEXT.too_slow).unwrap(),
+                duel::Action::Winner { player } => {
+                    jingle(VICTORY);
+                    winner = player as usize;
+                    ufmt::uwriteln!(&mut serial, "{} {} {}", TEXT.player, player + 1, TEXT.wins).unwrap();
                 }
-                break;
-            }
-            arduino_hal::delay_ms(1);
-            elapsed += 1;
+            });
+        }
+
+        animator.blink(PLAYER_COLORS[winner], 300, 300, 3, millis::millis());
+        loop {
+            led.set(animator.update(millis::millis()));
         }
+    }
 
-        set_led_color(&Color::None, &mut led_r, &mut led_g, &mut led_b);
+    ufmt::uwriteln!(&mut serial, "{}: {}", TEXT.level, TEXT.levels[level as usize]).unwrap();
+    if events {
+        ufmt::uwriteln!(&mut serial, "{} level={}", GAME_START, level.name()).unwrap();
+    }
+    let mut game = Game::new(level, rng);
+    let mut board = Hardware {
+        led: &mut led,
+        buttons: &buttons,
+        serial: &mut serial,
+        events,
+        foul: None,
+    };
+    reaction::run(&mut game, &mut board);
 
-        if correct_pressed {
-            play_success_tone(&mut buzzer);
-            wins += 1;
-            let _ = serial.write_str("Round ");
-            ufmt::uwriteln!(&mut serial, "s {}", round + 1).unwrap();
-        } else {
-            play_failure_tone(&mut buzzer);
-            let _ = serial.write_str("Round ");
-            ufmt::uwriteln!(&mut serial, "f {}", round + 1).unwrap();
+    let reactions = game.reactions();
+    if let (Some(mean), Some(best), Some(worst), Some(std_dev)) =
+        (reactions.mean(), reactions.min(), reactions.max(), reactions.std_dev())
+    {
+        ufmt::uwriteln!(
+            &mut serial,
+            "{} {}: {} us, {}: {} us, {}: {} us, {}: {} us",
+            TEXT.reaction,
+            TEXT.mean,
+            mean,
+            TEXT.best,
+            best,
+            TEXT.worst,
+            worst,
+            TEXT.std_dev,
+            std_dev
+        )
+        .unwrap();
+    }
+
+    ufmt::uwrite!(&mut serial, "{}:", TEXT.all_fouls).unwrap();
+    for (i, foul) in [Foul::PrePressed, Foul::FalseStart, Foul::Mashing].into_iter().enumerate() {
+        let separator = if i == 0 { " " } else { ", " };
+        ufmt::uwrite!(&mut serial, "{}{} {}", separator, TEXT.fouls[foul as usize], game.fouls(foul)).unwrap();
+    }
+    ufmt::uwriteln!(&mut serial, "").unwrap();
+
+    let score = game.score();
+    let best_reaction = reactions.min().unwrap_or(NO_REACTION);
+    if scores.rank(level, score, best_reaction).is_some() {
+        // Red and green pick a letter, blue confirms it and yellow goes back.
+        ufmt::uwriteln!(&mut serial, "{}", TEXT.new_high_score).unwrap();
+        let mut initials = *b"AAA";
+        let mut position = 0;
+        while position < initials.len() {
+            let letter = initials[position] as char;
+            ufmt::uwriteln!(&mut serial, "{} {}: {}", TEXT.initial, position + 1, letter).unwrap();
+
+            let mut pressed = 0;
+            while pressed == 0 {
+                pressed = held(&buttons);
+            }
+            let letter = &mut initials[position];
+            match pressed.trailing_zeros() {
+                0 => *letter = if *letter == b'A' { b'Z' } else { *letter - 1 },
+                1 => *letter = if *letter == b'Z' { b'A' } else { *letter + 1 },
+                2 => position += 1,
+                _ => position = position.saturating_sub(1),
+            }
+            arduino_hal::delay_ms(20);
+            while held(&buttons) != 0 {}
+            arduino_hal::delay_ms(20);
         }
 
-        arduino_hal::delay_ms(1000);
+        scores.insert(Entry { level, score, best_reaction, initials });
+        persist::store(&mut eep, SCORES_ADDR, &scores.to_bytes());
     }
 
-    if wins >= WIN_THRESHOLD {
-        ufmt::uwriteln!(&mut serial, "vyhra {}", wins).unwrap();
-        for _ in 0..5 {
-            led_g.toggle();
-            arduino_hal::delay_ms(300);
+    ufmt::uwriteln!(&mut serial, "{} {}", TEXT.high_scores, TEXT.levels[level as usize]).unwrap();
+    for (place, entry) in scores.entries(level).iter().enumerate() {
+        ufmt::uwrite!(&mut serial, "{}. {} {}", place + 1, entry.initials(), entry.score).unwrap();
+        if entry.best_reaction == NO_REACTION {
+            ufmt::uwriteln!(&mut serial, "").unwrap();
+        } else {
+            ufmt::uwriteln!(&mut serial, " {} us", entry.best_reaction).unwrap();
         }
+    }
+
+    if game.is_won() {
+        jingle(VICTORY);
+        ufmt::uwriteln!(&mut serial, "{} {}", TEXT.won, score).unwrap();
+        animator.blink(Rgb::GREEN, 300, 300, 3, millis::millis());
     } else {
-        ufmt::uwriteln!(&mut serial, "prohra {}", wins).unwrap();
-        for _ in 0..5 {
-            led_r.toggle();
-            arduino_hal::delay_ms(300);
-        }
+        jingle(DEFEAT);
+        ufmt::uwriteln!(&mut serial, "{} {}", TEXT.lost, score).unwrap();
+        animator.blink(Rgb::RED, 300, 300, 3, millis::millis());
     }
 
     loop {
-        arduino_hal::delay_ms(1000);
+        led.set(animator.update(millis::millis()));
     }
 }
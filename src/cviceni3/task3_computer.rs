@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+// Only part of what the firmware uses is exercised here.
+#[allow(dead_code)]
+#[path = "../common"]
+mod common {
+    pub mod animation;
+    pub mod color;
+    pub mod fixed;
+    pub mod melody;
+    pub mod random;
+    pub mod report;
+    pub mod stats;
+}
+mod difficulty;
+#[allow(dead_code)]
+mod duel;
+#[allow(dead_code)]
+mod events;
+#[allow(dead_code)]
+mod reaction;
+#[allow(dead_code)]
+mod scores;
+#[allow(dead_code)]
+mod simon;
+
+use common::animation::{self, Animator, Keyframe, Repeat, Transition};
+use common::color::Rgb;
+use common::melody::{self, Note};
+use common::random::{Entropy, Rng};
+use common::report::Report;
+use difficulty::Level;
+use duel::{Cue, Duel, TARGET};
+use reaction::{Action, Board, Color, Foul, Game, ALL_BUTTONS, MASH_WINDOW};
+use scores::{Entry, Table, PER_LEVEL};
+use simon::{Outcome, Phase, Simon, MAX_LENGTH};
+
+const USAGE: &str = "Usage:
+  task3_computer random [seed]
+  task3_computer simon
+  task3_computer reaction
+  task3_computer scores
+  task3_computer duel
+  task3_computer animation
+  task3_computer melody
+  task3_computer log <port> [file.csv]";
+
+const CSV_HEADER: &str = "game,event,level,round,result,reaction_ms,foul,count,score,rounds";
+
+// How far the simulated clock moves between two looks at the buttons, in µs.
+const TICK: u32 = 100;
+
+const SAMPLES: u32 = 1_000_000;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("random") if args.len() <= 3 => {
+            let seed = match args.get(2) {
+                Some(seed) => seed.parse()?,
+                None => 0xDEADBEEF,
+            };
+            if !check_random(seed) {
+                std::process::exit(1);
+            }
+        }
+        Some("reaction") if args.len() == 2 => {
+            if !check_reaction() {
+                std::process::exit(1);
+            }
+        }
+        Some("scores") if args.len() == 2 => {
+            if !check_scores() {
+                std::process::exit(1);
+            }
+        }
+        Some("duel") if args.len() == 2 => {
+            if !check_duel() {
+                std::process::exit(1);
+            }
+        }
+        Some("simon") if args.len() == 2 => {
+            if !check_simon() {
+                std::process::exit(1);
+            }
+        }
+        Some("animation") if args.len() == 2 => {
+            if !check_animation() {
+                std::process::exit(1);
+            }
+        }
+        Some("melody") if args.len() == 2 => {
+            if !check_melody() {
+                std::process::exit(1);
+            }
+        }
+        Some("log") if args.len() == 3 || args.len() == 4 => log(&args[2], args.get(3).map(String::as_str))?,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the usual quick statistical checks on the generator the game uses,
+/// returns whether all of them passed.
+fn check_random(seed: u32) -> bool {
+    let mut report = Report::new();
+
+    let mut rng = Rng::new(seed);
+    let words: Vec<u32> = (0..SAMPLES).map(|_| rng.next_u32()).collect();
+
+    // Share of one bits, as a z-score.
+    let ones: u64 = words.iter().map(|w| w.count_ones() as u64).sum();
+    let bits = SAMPLES as f64 * 32.0;
+    let z = (ones as f64 - bits / 2.0) / (bits / 4.0).sqrt();
+    report.value("monobit z", z, z.abs() < 4.0);
+
+    // Every byte position should hit all 256 values evenly.
+    for shift in [0, 8, 16, 24] {
+        let mut counts = [0u32; 256];
+        for w in &words {
+            counts[((w >> shift) & 0xFF) as usize] += 1;
+        }
+        let chi = chi_square(&counts);
+        report.value(&format!("bytes >> {} chi2 (df 255)", shift), chi, within(chi, 255.0));
+    }
+
+    // Neighbouring outputs should not predict each other.
+    let n = SAMPLES as f64 - 1.0;
+    let x: Vec<f64> = words.iter().map(|&w| w as f64).collect();
+    let mean = x.iter().sum::<f64>() / x.len() as f64;
+    let var: f64 = x.iter().map(|v| (v - mean).powi(2)).sum();
+    let cov: f64 = x.windows(2).map(|p| (p[0] - mean) * (p[1] - mean)).sum();
+    let r = cov / var;
+    report.value("serial correlation", r, r.abs() < 4.0 / n.sqrt());
+
+    // What the game draws: four colors and display times.
+    let mut colors = [0u32; 4];
+    let mut repeats = 0u32;
+    let mut last = u32::MAX;
+    for _ in 0..SAMPLES {
+        let color = rng.below(4);
+        colors[color as usize] += 1;
+        if color == last {
+            repeats += 1;
+        }
+        last = color;
+    }
+    let chi = chi_square(&colors);
+    report.value("colors chi2 (df 3)", chi, chi < 16.27);
+    let expected = (SAMPLES - 1) as f64 / 4.0;
+    let z = (repeats as f64 - expected) / (expected * 0.75).sqrt();
+    report.value("same color twice z", z, z.abs() < 4.0);
+
+    let mut times = vec![0u32; 701];
+    for _ in 0..SAMPLES {
+        times[(rng.range(800, 1500) - 800) as usize] += 1;
+    }
+    let chi = chi_square(&times);
+    report.value("display time chi2 (df 700)", chi, within(chi, 700.0));
+
+    // Boots whose entropy differs in a single bit must get unrelated seeds.
+    let mut flipped = 0u64;
+    let mut trials = 0u64;
+    for value in 0..1000u32 {
+        let mut base = Entropy::new();
+        base.add(value);
+        for bit in 0..32 {
+            let mut other = Entropy::new();
+            other.add(value ^ (1 << bit));
+            flipped += (base.seed() ^ other.seed()).count_ones() as u64;
+            trials += 1;
+        }
+    }
+    let avalanche = flipped as f64 / trials as f64;
+    report.value("seed avalanche (bits of 32)", avalanche, (avalanche - 16.0).abs() < 0.5);
+
+    report.passed()
+}
+
+fn chi_square(counts: &[u32]) -> f64 {
+    let total: u32 = counts.iter().sum();
+    let expected = total as f64 / counts.len() as f64;
+    counts.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum()
+}
+
+// Within five standard deviations of the chi-square mean.
+fn within(chi: f64, df: f64) -> bool {
+    (chi - df).abs() < 5.0 * (2.0 * df).sqrt()
+}
+
+/// Plays scripted games against the Simon rules, returns whether all of them
+/// went as expected.
+fn check_simon() -> bool {
+    let mut report = Report::new();
+
+    // A player who never makes a mistake.
+    let mut simon = Simon::new();
+    let mut rng = Rng::new(1);
+    let mut perfect = true;
+    for round in 1..=MAX_LENGTH {
+        perfect &= simon.next_round(rng.below(4) as u8) == Phase::Showing;
+        perfect &= simon.press(0) == Outcome::Ignored;
+        simon.shown();
+        let sequence = simon.sequence().to_vec();
+        perfect &= sequence.len() == round;
+        for (i, &color) in sequence.iter().enumerate() {
+            let expected = if i + 1 == round { Outcome::RoundComplete } else { Outcome::Next };
+            perfect &= simon.press(color) == expected;
+        }
+        perfect &= simon.score() == round;
+    }
+    report.check("perfect game grows the sequence", perfect);
+    report.check("full sequence wins", simon.next_round(0) == Phase::Won && simon.score() == MAX_LENGTH);
+
+    // Rounds keep the colors of the previous ones.
+    let mut simon = Simon::new();
+    simon.next_round(2);
+    simon.shown();
+    simon.press(2);
+    simon.next_round(1);
+    report.check("sequence keeps earlier colors", simon.sequence() == [2, 1]);
+
+    // A wrong color in the third round scores two.
+    let mut simon = Simon::new();
+    for color in [0, 1, 3] {
+        simon.next_round(color);
+        simon.shown();
+        for &color in simon.sequence().to_vec().iter().take(2) {
+            simon.press(color);
+        }
+    }
+    let wrong = simon.press(2) == Outcome::Wrong;
+    report.check("wrong color loses", wrong && simon.phase() == Phase::Lost && simon.score() == 2);
+    report.check("presses after losing are ignored", simon.press(3) == Outcome::Ignored);
+    report.check("no new round after losing", simon.next_round(0) == Phase::Lost);
+
+    // Taking too long.
+    let mut simon = Simon::new();
+    simon.next_round(3);
+    simon.timeout();
+    report.check("timeout while showing is ignored", simon.phase() == Phase::Showing);
+    simon.shown();
+    simon.timeout();
+    report.check("timeout while listening loses", simon.phase() == Phase::Lost && simon.score() == 0);
+
+    report.passed()
+}
+
+/// Board whose buttons are pressed by `player`, given the color on the LED
+/// and the µs since it last changed.
+struct Scripted<F: FnMut(Option<Color>, u32) -> u8> {
+    now: u32,
+    shown: Option<Color>,
+    since: u32,
+    player: F,
+    actions: Vec<Action>,
+}
+
+impl<F: FnMut(Option<Color>, u32) -> u8> Scripted<F> {
+    // Starts just before the clock wraps, the game has to cope with that.
+    fn new(player: F) -> Self {
+        let now = u32::MAX - 5_000_000;
+        Scripted { now, shown: None, since: now, player, actions: Vec::new() }
+    }
+
+    fn rounds(&self) -> impl Iterator<Item = (bool, Option<u32>)> + '_ {
+        self.actions.iter().filter_map(|action| match *action {
+            Action::Round { correct, reaction, .. } => Some((correct, reaction)),
+            _ => None,
+        })
+    }
+
+    fn shown(&self) -> impl Iterator<Item = Color> + '_ {
+        self.actions.iter().filter_map(|action| match *action {
+            Action::Show(color) => Some(color),
+            _ => None,
+        })
+    }
+}
+
+impl<F: FnMut(Option<Color>, u32) -> u8> Board for Scripted<F> {
+    fn micros(&mut self) -> u32 {
+        self.now = self.now.wrapping_add(TICK);
+        self.now
+    }
+
+    fn buttons(&mut self) -> u8 {
+        (self.player)(self.shown, self.now.wrapping_sub(self.since))
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Show(color) => {
+                self.shown = Some(color);
+                self.since = self.now;
+            }
+            Action::Clear => {
+                self.shown = None;
+                self.since = self.now;
+            }
+            _ => {}
+        }
+        self.actions.push(action);
+    }
+}
+
+fn button(color: Color) -> u8 {
+    match Color::BUTTONS.iter().position(|&c| c == color) {
+        Some(i) => 1 << i,
+        None => 0,
+    }
+}
+
+fn play<F: FnMut(Option<Color>, u32) -> u8>(level: Level, seed: u32, player: F) -> (Game, Scripted<F>) {
+    let mut game = Game::new(level, Rng::new(seed));
+    let mut board = Scripted::new(player);
+    reaction::run(&mut game, &mut board);
+    (game, board)
+}
+
+/// Plays scripted games of the reaction game, returns whether all of them
+/// went as expected.
+fn check_reaction() -> bool {
+    let mut report = Report::new();
+
+    for level in [Level::Easy, Level::Normal, Level::Hard, Level::Adaptive] {
+        // Presses the right button after 250 ms and leaves decoys alone.
+        let (game, board) = play(level, 7, |color, since| match color {
+            Some(color) if since >= 250_000 => button(color),
+            _ => 0,
+        });
+        let all_correct = board.rounds().all(|(correct, _)| correct);
+        let reactions: Vec<u32> = board.rounds().filter_map(|(_, reaction)| reaction).collect();
+        let timed = reactions.iter().all(|reaction| (250_000..250_000 + 2 * TICK).contains(reaction));
+        let over = board.actions.last() == Some(&Action::GameOver { score: game.rounds(), rounds: game.rounds() });
+        report.check(&format!("{} perfect player wins", level.name()), all_correct && timed && over && game.is_won());
+        let counted = game.reactions().count() as usize == reactions.len();
+        report.check(&format!("{} reaction stats", level.name()), counted);
+    }
+
+    // Never presses anything, only decoys are won.
+    let mut decoys = 0;
+    let mut idle = true;
+    for seed in 0..20 {
+        let (game, board) = play(Level::Hard, seed, |_, _| 0);
+        let expected = board.shown().filter(|&color| color == Color::Decoy).count();
+        decoys += expected;
+        idle &= game.wins() as usize == expected && game.reactions().count() == 0;
+    }
+    report.check("idle player wins only decoys", idle && decoys > 0);
+
+    // Taps red shortly after every color.
+    let (game, board) = play(Level::Normal, 3, |color, since| match color {
+        Some(_) if (100_000..150_000).contains(&since) => button(Color::Red),
+        _ => 0,
+    });
+    let reds = board.shown().filter(|&color| color == Color::Red).count();
+    report.check("red only player", game.wins() as usize == reds && game.score() == game.wins());
+
+    // Presses only on decoys, so loses every round.
+    let (game, board) = play(Level::Hard, 11, |color, _| match color {
+        Some(Color::Decoy) => button(Color::Red),
+        _ => 0,
+    });
+    let had_decoy = board.shown().any(|color| color == Color::Decoy);
+    report.check("pressing on a decoy loses", had_decoy && game.wins() == 0);
+
+    // Keeps red held the whole game.
+    let (game, _) = play(Level::Normal, 5, |_, _| button(Color::Red));
+    let fouls = game.fouls(Foul::PrePressed);
+    report.check("held button is pre-pressed", fouls == game.rounds() && game.wins() == 0 && game.score() == 0);
+
+    // Plays perfectly but also presses between rounds.
+    let (game, _) = play(Level::Easy, 5, |color, since| match color {
+        Some(color) if since >= 250_000 => button(color),
+        None if (500_000..550_000).contains(&since) => button(Color::Blue),
+        _ => 0,
+    });
+    let fouls = game.fouls(Foul::FalseStart);
+    report.check("false starts are penalized", fouls == game.rounds() && game.score() == 0 && !game.is_won());
+
+    // Mashes all buttons at once, or two right after each other.
+    let (game, _) = play(Level::Normal, 5, |color, _| if color.is_some() { ALL_BUTTONS } else { 0 });
+    report.check("all buttons at once is mashing", game.fouls(Foul::Mashing) == game.rounds() && game.score() == 0);
+    let (game, _) = play(Level::Easy, 9, |color, since| match color {
+        Some(color) if since >= 250_000 + MASH_WINDOW / 2 => button(color) | button(Color::Red) | button(Color::Green),
+        Some(color) if since >= 250_000 => button(color),
+        _ => 0,
+    });
+    report.check("quick second button is mashing", game.fouls(Foul::Mashing) == game.rounds() && game.score() == 0);
+
+    // A press bouncing on the same button is not mashing.
+    let (game, _) = play(Level::Easy, 9, |color, since| match color {
+        Some(color) if since >= 250_000 && since / 5_000 % 2 == 0 => button(color),
+        _ => 0,
+    });
+    report.check("bouncing button is fine", game.fouls(Foul::Mashing) == 0 && game.wins() == game.rounds());
+
+    // Holds the button until the round ends and it bounces on release.
+    let mut held = 0;
+    let (game, _) = play(Level::Easy, 9, |color, since| match color {
+        Some(color) if since >= 250_000 => {
+            held = button(color);
+            held
+        }
+        None if since < 10_000 && since / 2_000 % 2 == 1 => held,
+        _ => 0,
+    });
+    report.check("bouncing release is no false start", game.fouls(Foul::FalseStart) == 0 && game.wins() == game.rounds());
+
+    report.passed()
+}
+
+/// Fills the high score table with games of different levels, returns
+/// whether each level kept its own ranking.
+fn check_scores() -> bool {
+    let mut report = Report::new();
+    let entry = |level, score, best_reaction| Entry { level, score, best_reaction, initials: *b"ABC" };
+    let ranking = |table: &Table, level| table.entries(level).iter().map(|e| (e.score, e.best_reaction)).collect::<Vec<_>>();
+
+    let mut table = Table::new();
+    // Hard games play twice the rounds, they must not crowd out easy ones.
+    for score in 1..=8 {
+        table.insert(entry(Level::Hard, score, 300_000));
+    }
+    let easy = table.insert(entry(Level::Easy, 3, 250_000));
+    table.insert(entry(Level::Easy, 3, 200_000));
+    table.insert(entry(Level::Adaptive, 9, 150_000));
+    let apart = easy == Some(0) && ranking(&table, Level::Easy) == [(3, 200_000), (3, 250_000)];
+    report.check("levels are ranked apart", apart);
+    let hard = ranking(&table, Level::Hard);
+    report.check("a full level drops its worst", hard.len() == PER_LEVEL && hard[0].0 == 8 && hard[PER_LEVEL - 1].0 == 4);
+    let slower = table.rank(Level::Hard, 4, 400_000);
+    let faster = table.rank(Level::Hard, 4, 200_000);
+    report.check("too low for a full level", slower.is_none() && faster == Some(4));
+    report.check("no correct round, no place", table.rank(Level::Normal, 0, 100_000).is_none());
+
+    let restored = Table::from_bytes(&table.to_bytes());
+    let same = [Level::Easy, Level::Normal, Level::Hard, Level::Adaptive].iter().all(|&level| {
+        ranking(&restored, level) == ranking(&table, level)
+            && restored.entries(level).iter().all(|e| e.level == level && e.initials() == "ABC")
+    });
+    report.check("table survives the EEPROM", same);
+
+    report.passed()
+}
+
+/// Compares the keyframe easing with floating point and plays a few
+/// animations, returns whether they all came out right.
+fn check_animation() -> bool {
+    let mut report = Report::new();
+
+    let worst = (0..=255u8)
+        .map(|t| {
+            let x = t as f64 / 255.0;
+            (animation::smoothstep(t) as f64 - 255.0 * x * x * (3.0 - 2.0 * x)).abs()
+        })
+        .fold(0.0, f64::max);
+    report.value("smoothstep worst error", worst, worst < 1.0);
+    let ends = animation::smoothstep(0) == 0 && animation::smoothstep(255) == 255;
+    let rising = (1..=255u8).all(|t| animation::smoothstep(t) >= animation::smoothstep(t - 1));
+    let symmetric = (0..=255u8).all(|t| (animation::smoothstep(t) as i32 + animation::smoothstep(255 - t) as i32 - 255).abs() <= 1);
+    report.check("smoothstep ends, rise and symmetry", ends && rising && symmetric);
+
+    let from = Rgb::new(0, 100, 200);
+    let to = Rgb::new(200, 100, 0);
+    let linear = Keyframe::new(to, 1000, Transition::Linear);
+    let halfway = animation::interpolate(from, &linear, 500);
+    let linear_ok = animation::interpolate(from, &linear, 0) == from
+        && animation::interpolate(from, &linear, 1000) == to
+        && animation::interpolate(from, &linear, 5000) == to
+        && (halfway.r as i32 - 100).abs() <= 1
+        && halfway.g == 100
+        && (halfway.b as i32 - 100).abs() <= 1;
+    report.check("linear interpolation", linear_ok);
+    let smooth = Keyframe::new(to, 1000, Transition::Smooth);
+    let early = animation::interpolate(from, &smooth, 100);
+    let smooth_ok = early.r < animation::interpolate(from, &linear, 100).r
+        && animation::interpolate(from, &smooth, 1000) == to;
+    report.check("smooth interpolation starts slowly", smooth_ok);
+    let step = Keyframe::new(to, 1000, Transition::Step);
+    let instant = Keyframe::new(to, 0, Transition::Linear);
+    let step_ok = animation::interpolate(from, &step, 0) == to && animation::interpolate(from, &instant, 0) == to;
+    report.check("step and zero duration jump", step_ok);
+
+    let mut animator = Animator::new();
+    let now = u32::MAX - 500;
+    animator.fade(Rgb::WHITE, 1000, now);
+    animator.update(now.wrapping_add(400));
+    animator.play(&[], Repeat::Forever, now.wrapping_add(400));
+    let shown = animator.update(now.wrapping_add(400));
+    let empty = !animator.is_running() && animator.update(now.wrapping_add(5000)) == shown && shown != Rgb::OFF;
+    report.check("no frames stops and keeps the color", empty);
+
+    animator.blink(Rgb::RED, 100, 100, 2, now);
+    let blinks: Vec<Rgb> = (0..5).map(|i| animator.update(now.wrapping_add(i * 100 + 50))).collect();
+    let blink_ok = blinks == [Rgb::RED, Rgb::OFF, Rgb::RED, Rgb::OFF, Rgb::OFF] && !animator.is_running();
+    report.check("blink twice across the clock wrap", blink_ok);
+
+    animator.fade(Rgb::BLUE, 100, now);
+    animator.update(now.wrapping_add(200));
+    animator.blink(Rgb::RED, 100, 100, 0, now.wrapping_add(200));
+    let none = (0..5).all(|i| animator.update(now.wrapping_add(200 + i * 100 + 50)) == Rgb::BLUE);
+    report.check("blink zero times does nothing", none && !animator.is_running());
+
+    report.passed()
+}
+
+/// Notes of an RTTTL melody, `None` if it does not parse.
+fn notes(text: &str) -> Option<Vec<(u16, u16)>> {
+    let notes = melody::parse(text).ok()?;
+    Some(notes.map(|Note { frequency, duration }| (frequency, duration)).collect())
+}
+
+/// Parses RTTTL melodies with every kind of note, returns whether they all
+/// came out right.
+fn check_melody() -> bool {
+    let mut report = Report::new();
+
+    // A whole note takes 2 s at 120 beats per minute.
+    let durations = notes("x:d=4,o=5,b=120:1c,2c,c,8c,16c,32c");
+    report.check("durations", durations == Some(vec![(523, 2000), (523, 1000), (523, 500), (523, 250), (523, 125), (523, 62)]));
+    let dots = notes("x:d=4,o=5,b=120:c.,8c.,c.6,c6.");
+    report.check("dots before and after the octave", dots == Some(vec![(523, 750), (523, 375), (1046, 750), (1046, 750)]));
+    let octaves = notes("x:d=4,o=5,b=120:a4,a,a6,a7,a8");
+    report.check("octaves", octaves == Some(vec![(440, 500), (880, 500), (1760, 500), (3520, 500), (7040, 500)]));
+    let sharps = notes("x:d=4,o=5,b=120:c#,f#4,h,b#,b#8");
+    report.check("sharps and B# to the next C", sharps == Some(vec![(554, 500), (370, 500), (987, 500), (1046, 500), (4186, 500)]));
+    let pauses = notes("x:d=8,o=5,b=120:p,2p.,c");
+    report.check("pauses", pauses == Some(vec![(0, 250), (0, 1500), (523, 250)]));
+    let defaults = notes("x::c,d");
+    report.check("default settings", defaults == Some(vec![(1046, 952), (1174, 952)]));
+    let total = melody::parse("x:d=4,o=5,b=120:c,8p,2g.").map(|notes| notes.duration());
+    report.check("melody length", total == Ok(500 + 250 + 1500));
+
+    let errors = [
+        ("no settings", melody::Error::Sections),
+        ("x:d=3:c", melody::Error::Setting),
+        ("x:o=9:c", melody::Error::Setting),
+        ("x:q=1:c", melody::Error::Setting),
+        ("x:d=4:c,x,d", melody::Error::Note(1)),
+        ("x:d=4:c,3d", melody::Error::Note(1)),
+        ("x:d=4:c,e,a9", melody::Error::Note(2)),
+        ("x:d=4:c,d#x", melody::Error::Note(1)),
+    ];
+    let rejected = errors.iter().all(|&(text, error)| melody::parse(text).err() == Some(error));
+    report.check("broken melodies are rejected", rejected);
+
+    report.passed()
+}
+
+/// Plays a duel where `players` get the cue with the time it was shown and
+/// the current time and answer with the presses captured since the last step.
+fn duel<F: FnMut(Option<(Cue, u32)>, u32) -> [Option<u32>; 4]>(seed: u32, mut players: F) -> (Duel, Vec<duel::Action>) {
+    let mut duel = Duel::new(Rng::new(seed));
+    let mut actions = Vec::new();
+    let mut cue = None;
+    let mut now = u32::MAX - 5_000_000;
+    for _ in 0..2_000_000 {
+        if duel.is_over() {
+            break;
+        }
+        now = now.wrapping_add(TICK);
+        let presses = players(cue, now);
+        duel.step(now, presses, |action| {
+            match action {
+                duel::Action::Show(shown) => cue = Some((shown, now)),
+                duel::Action::Clear => cue = None,
+                _ => {}
+            }
+            actions.push(action);
+        });
+    }
+    (duel, actions)
+}
+
+/// The press captured at `at`, delivered in the step that covers it.
+fn once(now: u32, at: u32) -> Option<u32> {
+    (now.wrapping_sub(at) < TICK).then_some(at)
+}
+
+/// Presses of the right (or wrong) button of `player` at `delay` after the cue.
+fn answer(cue: Option<(Cue, u32)>, now: u32, player: usize, delay: u32, right: bool) -> [Option<u32>; 4] {
+    let mut presses = [None; 4];
+    if let Some((cue, shown)) = cue {
+        let first = (cue == Cue::First) == right;
+        presses[player * 2 + if first { 0 } else { 1 }] = once(now, shown.wrapping_add(delay));
+    }
+    presses
+}
+
+fn merge(a: [Option<u32>; 4], b: [Option<u32>; 4]) -> [Option<u32>; 4] {
+    [a[0].or(b[0]), a[1].or(b[1]), a[2].or(b[2]), a[3].or(b[3])]
+}
+
+/// Plays scripted duels, returns whether all of them went as expected.
+fn check_duel() -> bool {
+    let mut report = Report::new();
+    let count = |actions: &[duel::Action], f: fn(&duel::Action) -> bool| actions.iter().filter(|a| f(a)).count();
+
+    let (game, actions) = duel(1, |cue, now| merge(answer(cue, now, 0, 200_000, true), answer(cue, now, 1, 300_000, true)));
+    let last = actions.last() == Some(&duel::Action::Winner { player: 0 });
+    report.check("faster player wins", game.is_over() && game.scores() == [TARGET, 0] && last);
+
+    // Both presses arrive in the same step, the timestamps decide.
+    let (game, _) = duel(2, |cue, now| {
+        let mut presses = answer(cue, now, 0, 200_000 + TICK / 2, true);
+        presses = merge(presses, answer(cue, now, 1, 200_000 + TICK / 2, true).map(|at| at.map(|at| at - TICK / 4)));
+        presses
+    });
+    report.check("timestamps break a close call", game.scores() == [0, TARGET]);
+
+    // Exactly the same time is a tie, every round.
+    let (game, actions) = duel(3, |cue, now| merge(answer(cue, now, 0, 200_000, true), answer(cue, now, 1, 200_000, true)));
+    let ties = count(&actions, |a| *a == duel::Action::Tie);
+    report.check("identical times tie", !game.is_over() && ties > 10 && game.scores() == [0, 0]);
+
+    // Player 1 keeps pressing every half a second while waiting for the cue.
+    let (game, actions) = duel(4, |cue, now| {
+        let mut presses = answer(cue, now, 1, 400_000, true);
+        if cue.is_none() && now % 500_000 < TICK {
+            presses[0] = Some(now);
+        }
+        presses
+    });
+    let false_starts = count(&actions, |a| matches!(a, duel::Action::FalseStart { player: 0 }));
+    report.check("false start gives the other a point", game.scores() == [0, TARGET] && false_starts == TARGET as usize);
+
+    // Player 1 presses the wrong button first, player 2 is slower but right.
+    let (game, actions) = duel(5, |cue, now| merge(answer(cue, now, 0, 200_000, false), answer(cue, now, 1, 400_000, true)));
+    let wrong = count(&actions, |a| matches!(a, duel::Action::Wrong { player: 0 }));
+    report.check("wrong button hands the round over", game.scores() == [0, TARGET] && wrong == TARGET as usize);
+
+    // Right and wrong in one step, the earlier one counts.
+    let (game, _) = duel(6, |cue, now| {
+        let right = answer(cue, now, 0, 200_000 + TICK / 2, true);
+        let wrong = answer(cue, now, 0, 200_000 + TICK / 2, false).map(|at| at.map(|at| at + TICK / 4));
+        merge(merge(right, wrong), answer(cue, now, 1, 400_000, true))
+    });
+    report.check("right before wrong still counts", game.scores() == [TARGET, 0]);
+
+    // Nobody presses.
+    let (game, actions) = duel(7, |_, _| [None; 4]);
+    let timeouts = count(&actions, |a| *a == duel::Action::Timeout);
+    report.check("nobody pressing times out", game.scores() == [0, 0] && timeouts > 10);
+
+    report.passed()
+}
+
+/// Writes the events of the reaction games played on the board as CSV rows,
+/// to `path` or standard output, and echoes the other lines to standard
+/// error. Keeps going across board resets until interrupted.
+fn log(port: &str, path: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut port = serialport::new(port, 57600)
+        .timeout(Duration::from_millis(100))
+        .open()?;
+    let mut out: Box<dyn Write> = match path {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    writeln!(out, "{}", CSV_HEADER)?;
+
+    // Opening the port resets the board.
+    std::thread::sleep(Duration::from_secs(2));
+
+    let mut game = 0;
+    let mut asked: Option<Instant> = None;
+    let mut line = Vec::new();
+    let mut buf = [0u8; 64];
+    loop {
+        // The board only listens while it waits for the start button, ask
+        // again every second to catch the next reset too.
+        if asked.is_none_or(|at| at.elapsed() >= Duration::from_secs(1)) {
+            port.write_all(&[events::ENABLE])?;
+            asked = Some(Instant::now());
+        }
+
+        let count = match port.read(&mut buf) {
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for &byte in &buf[..count] {
+            match byte {
+                b'\n' => {
+                    let text = String::from_utf8_lossy(&line);
+                    match events::parse(&text) {
+                        Some((name, fields)) => {
+                            if name == events::GAME_START {
+                                game += 1;
+                            }
+                            let fields: HashMap<&str, &str> = fields.collect();
+                            let row: Vec<&str> = ["level", "n", "result", "ms", "kind", "count", "score", "rounds"]
+                                .iter()
+                                .map(|key| fields.get(key).copied().unwrap_or(""))
+                                .collect();
+                            writeln!(out, "{},{},{}", game, name, row.join(","))?;
+                            out.flush()?;
+                        }
+                        None => eprintln!("{}", text),
+                    }
+                    line.clear();
+                }
+                b'\r' => {}
+                _ => line.push(byte),
+            }
+        }
+    }
+}
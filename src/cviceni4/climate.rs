@@ -0,0 +1,130 @@
+//! What the DHT11 readings mean to a person: dew point and heat index, and
+//! how much the readings can be trusted.
+//!
+//! Temperatures are fixed-point in tenths of °C, humidity in whole percent
+//! as the DHT11 gives it.
+
+use crate::common::fixed::{self, divide_rounded, isqrt};
+
+/// Readings older than this many seconds are shown as stale.
+pub const STALE_AFTER: u16 = 10;
+
+// ln 2 with 16 fraction bits.
+const LN_2: i64 = 45426;
+
+// Magnus formula constants, b with 16 fraction bits and c in hundredths of
+// °C.
+const MAGNUS_B: i64 = 1_154_744;
+const MAGNUS_C: i64 = 24312;
+
+/// Natural logarithm of `value` (at least 1) with 16 fraction bits.
+fn ln(value: u32) -> i64 {
+    (fixed::log2(value) as i64 * LN_2) >> 16
+}
+
+/// Temperature at which the air would be saturated, from the Magnus
+/// formula.
+pub fn dew_point(celsius: i32, humidity: u8) -> i32 {
+    let hundredths = celsius as i64 * 10;
+    let gamma = ln(humidity.max(1) as u32) - ln(100) + MAGNUS_B * hundredths / (MAGNUS_C + hundredths);
+    divide_rounded((MAGNUS_C * gamma / (MAGNUS_B - gamma)) as i32, 10)
+}
+
+/// How hot it feels, from the NOAA regression. Below about 27 °C it is
+/// close to the actual temperature.
+pub fn heat_index(celsius: i32, humidity: u8) -> i32 {
+    let t = (divide_rounded(celsius * 9, 5) + 320) as i64;
+    let rh = humidity as i64;
+
+    // Steadman's simple formula, good enough where it stays under 80 °F.
+    let simple = (t + 610 + (t - 680) * 6 / 5 + rh * 47 / 50) / 2;
+    let fahrenheit = if (simple + t) / 2 < 800 {
+        simple
+    } else {
+        // Rothfusz in tenths of °F times 10^8, which keeps the coefficients
+        // whole.
+        let sum = -4_237_900_000 * 10
+            + 204_901_523 * t
+            + 1_014_333_127 * rh * 10
+            - 22_475_541 * t * rh
+            - 683_783 * t * t / 10
+            - 5_481_717 * rh * rh * 10
+            + 122_874 * t * t * rh / 10
+            + 85_282 * t * rh * rh
+            - 199 * t * t * rh * rh / 10;
+        let mut hi = (sum + 50_000_000) / 100_000_000;
+
+        if rh < 13 && (800..=1120).contains(&t) {
+            // Thousandths of sqrt((17 - |T - 95|) / 17).
+            let root = isqrt(((170 - (t - 950).abs()) * 1_000_000 / 170) as u64) as i64;
+            hi -= (13 - rh) * root / 400;
+        } else if rh > 85 && (800..=870).contains(&t) {
+            hi += (rh - 85) * (870 - t) / 50;
+        }
+        hi
+    };
+
+    divide_rounded((fahrenheit as i32 - 320) * 5, 9)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Failure {
+    /// The bits arrived but did not add up.
+    Checksum,
+    /// The sensor did not answer or stopped halfway.
+    Timeout,
+}
+
+/// The last good DHT11 reading and what went wrong since.
+pub struct Readings {
+    last: Option<(i32, u8)>,
+    age: u16,
+    checksum_errors: u16,
+    timeouts: u16,
+}
+
+impl Readings {
+    pub const fn new() -> Self {
+        Readings { last: None, age: 0, checksum_errors: 0, timeouts: 0 }
+    }
+
+    /// Takes a reading of `celsius` in tenths and `humidity` percent.
+    pub fn good(&mut self, celsius: i32, humidity: u8) {
+        self.last = Some((celsius, humidity));
+        self.age = 0;
+    }
+
+    pub fn failed(&mut self, failure: Failure) {
+        let count = match failure {
+            Failure::Checksum => &mut self.checksum_errors,
+            Failure::Timeout => &mut self.timeouts,
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Called once a second.
+    pub fn tick(&mut self) {
+        self.age = self.age.saturating_add(1);
+    }
+
+    /// Temperature in tenths of °C and humidity of the last good reading.
+    pub fn last(&self) -> Option<(i32, u8)> {
+        self.last
+    }
+
+    /// Seconds since the last good reading.
+    pub fn age(&self) -> u16 {
+        self.age
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.last.is_some() && self.age >= STALE_AFTER
+    }
+
+    pub fn errors(&self, failure: Failure) -> u16 {
+        match failure {
+            Failure::Checksum => self.checksum_errors,
+            Failure::Timeout => self.timeouts,
+        }
+    }
+}
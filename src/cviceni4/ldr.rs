@@ -0,0 +1,61 @@
+//! Photoresistor (LDR) in a voltage divider as a rough lux meter.
+//!
+//! The LDR follows `R = R10 * (lux / 10)^-gamma`, with its resistance at
+//! 10 lux and gamma from the datasheet, 0.6 to 0.8 for the common CdS
+//! ones. Parts of the same type still differ by tens of percent, so the
+//! result is an estimate.
+
+use crate::common::fixed;
+
+/// The share of Vcc on the pin is in parts of this.
+pub const FULL_SCALE: u32 = 1 << 24;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Wiring {
+    /// Fixed resistor to Vcc, LDR to ground: darker is a higher voltage.
+    LdrToGround,
+    /// LDR to Vcc, fixed resistor to ground: lighter is a higher voltage.
+    LdrToVcc,
+}
+
+pub struct Ldr {
+    /// The other resistor of the divider, in Ω.
+    pub fixed: u32,
+    /// LDR resistance at 10 lux, in Ω.
+    pub r10: u32,
+    /// In hundredths.
+    pub gamma: u16,
+    pub wiring: Wiring,
+}
+
+/// Share of Vcc on the pin from a `bits`-wide `reading` against `reference`
+/// mV with the supply at `vcc` mV.
+pub fn ratio(reading: u32, bits: u8, reference: u32, vcc: u32) -> u32 {
+    let ratio = ((reading as u64) << (24 - bits)) * reference as u64 / vcc.max(1) as u64;
+    ratio.min(FULL_SCALE as u64) as u32
+}
+
+impl Ldr {
+    /// LDR resistance in Ω, `None` at either end of the range where the
+    /// divider says nothing.
+    pub fn resistance(&self, ratio: u32) -> Option<u32> {
+        if ratio == 0 || ratio >= FULL_SCALE {
+            return None;
+        }
+        let (upper, lower) = (FULL_SCALE - ratio, ratio);
+        let (numerator, denominator) = match self.wiring {
+            Wiring::LdrToGround => (lower, upper),
+            Wiring::LdrToVcc => (upper, lower),
+        };
+        let resistance = self.fixed as u64 * numerator as u64 / denominator as u64;
+        Some(resistance.min(u32::MAX as u64) as u32)
+    }
+
+    /// Illuminance in tenths of lux at `resistance` Ω.
+    pub fn lux(&self, resistance: u32) -> u32 {
+        // log2(lux * 10) = log2(100) + (log2(R10) - log2(R)) / gamma
+        let ratio = fixed::log2(self.r10) as i64 - fixed::log2(resistance) as i64;
+        let log = fixed::log2(100) as i64 + ratio * 100 / self.gamma.max(1) as i64;
+        fixed::exp2(log.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
@@ -0,0 +1,43 @@
+//! LM335 temperature sensor. It puts out 10 mV per kelvin, so an ADC reading
+//! becomes a temperature once the reference voltage is known.
+//!
+//! Temperatures are fixed-point in tenths of a degree.
+
+use crate::common::fixed::divide_rounded;
+
+/// Nominal bandgap reference of the ATmega328P in mV. Parts vary from 1.0 to
+/// 1.2 V, a gain error of up to 9 % or about 27 K at room temperature, so
+/// the actual value of a board is measured and passed in where it matters.
+pub const BANDGAP: u32 = 1100;
+
+/// 0 °C in hundredths of a kelvin.
+const ZERO_CELSIUS: i32 = 27315;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    /// The supply, measured against the bandgap so USB sag does not show
+    /// up as a temperature change.
+    AVcc,
+    /// The bandgap itself. The sensor gives 2.7 to 3.3 V around room
+    /// temperature, more than 1.1 V, so this needs the output divided down.
+    Internal,
+}
+
+/// Supply voltage in mV from a `reading` of the bandgap channel against
+/// AVcc, with the bandgap at `bandgap` mV.
+pub fn vcc(reading: u16, bandgap: u32) -> u32 {
+    (bandgap * 1024 + reading as u32 / 2) / (reading as u32).max(1)
+}
+
+/// Tenths of °C at the sensor from `reading` against `reference` mV,
+/// corrected by `offset` tenths.
+pub fn celsius(reading: u16, reference: u32, offset: i16) -> i32 {
+    // 10 mV per kelvin makes a hundredth of a mV a thousandth of a kelvin.
+    let millikelvin = (reading as u32 * reference * 100 + 512) / 1024;
+    divide_rounded(millikelvin as i32 - ZERO_CELSIUS * 10, 100) + offset as i32
+}
+
+/// Tenths of °F from tenths of °C.
+pub fn fahrenheit(celsius: i32) -> i32 {
+    divide_rounded(celsius * 9, 5) + 320
+}
@@ -2,11 +2,128 @@
 #![no_main]
 
 use ag_lcd::{Cursor, Display, LcdDisplay, Lines};
+use arduino_hal::adc::channel;
+use arduino_hal::eeprom::Eeprom;
 use embedded_dht_rs::dht11::Dht11;
+use embedded_dht_rs::SensorError;
 use embedded_hal::{delay::DelayNs, digital::OutputPin};
 use port_expander::dev::pcf8574::Pcf8574;
 use panic_halt as _;
 
+#[path = "../common/mod.rs"]
+mod common;
+mod climate;
+mod ldr;
+mod lm335;
+
+use common::analog::oversample;
+use common::fixed;
+use common::menu::{Event, Item, Key, Menu, COLUMNS};
+use common::persist;
+use climate::{Failure, Readings};
+use ldr::{Ldr, Wiring};
+use lm335::Reference;
+
+// Actions.
+const TEMPERATURE: u8 = 0;
+const LM335: u8 = 1;
+const LIGHT: u8 = 2;
+const HUMIDITY: u8 = 3;
+const DEW_POINT: u8 = 4;
+const HEAT_INDEX: u8 = 5;
+const DHT_ERRORS: u8 = 6;
+
+// Values.
+const UNITS: u8 = 0;
+const LOG: u8 = 1;
+const REFERENCE: u8 = 2;
+const OFFSET: u8 = 3;
+const LDR_FIXED: u8 = 4;
+const LDR_R10: u8 = 5;
+const LDR_GAMMA: u8 = 6;
+const LDR_WIRING: u8 = 7;
+const OVERSAMPLE: u8 = 8;
+const DIVIDER: u8 = 9;
+const BANDGAP: u8 = 10;
+
+const MENU: &[Item] = &[
+    Item::Submenu {
+        label: "DHT11",
+        items: &[
+            Item::Action { label: "Temperature", id: TEMPERATURE },
+            Item::Action { label: "Humidity", id: HUMIDITY },
+            Item::Action { label: "Dew point", id: DEW_POINT },
+            Item::Action { label: "Heat index", id: HEAT_INDEX },
+            Item::Action { label: "Errors", id: DHT_ERRORS },
+        ],
+    },
+    Item::Action { label: "LM335", id: LM335 },
+    Item::Action { label: "Light", id: LIGHT },
+    Item::Submenu {
+        label: "Settings",
+        items: &[
+            Item::Choice { label: "Units", id: UNITS, options: &["C", "F"] },
+            Item::Toggle { label: "Serial log", id: LOG },
+            Item::Choice { label: "Reference", id: REFERENCE, options: &["AVcc", "1.1V"] },
+            // What the LM335 output is divided by on the way to A0, 1.00
+            // when wired directly. The 1.1V reference needs 3 or more.
+            Item::Number { label: "Divider", id: DIVIDER, min: 100, max: 500, step: 1, decimals: 2 },
+            // In mV, shown as volts. With it at 1.100, it is 1100 times the
+            // supply a multimeter shows over the "ref" the LM335 logs with
+            // AVcc. Every reading and the supply voltage scale with it.
+            Item::Number { label: "Bandgap", id: BANDGAP, min: 1000, max: 1200, step: 1, decimals: 3 },
+            // With the bandgap set, what is left is the LM335's own error of
+            // a few degrees. Set it to what a good thermometer next to the
+            // sensor shows.
+            Item::Number { label: "Offset", id: OFFSET, min: -100, max: 100, step: 1, decimals: 1 },
+            Item::Submenu {
+                label: "Light sensor",
+                items: &[
+                    // Resistances in kΩ.
+                    Item::Number { label: "Resistor", id: LDR_FIXED, min: 10, max: 10000, step: 10, decimals: 1 },
+                    Item::Number { label: "R10", id: LDR_R10, min: 10, max: 10000, step: 5, decimals: 1 },
+                    Item::Number { label: "Gamma", id: LDR_GAMMA, min: 30, max: 150, step: 1, decimals: 2 },
+                    Item::Choice { label: "LDR to", id: LDR_WIRING, options: &["GND", "Vcc"] },
+                    // Extra bits of resolution.
+                    Item::Number { label: "Oversample", id: OVERSAMPLE, min: 0, max: 3, step: 1, decimals: 0 },
+                ],
+            },
+        ],
+    },
+];
+
+// Kept in the EEPROM, with what they start at before anything is saved.
+const SAVED: [(u8, i16); 10] = [
+    (UNITS, 0),
+    (REFERENCE, 0),
+    (DIVIDER, 100),
+    (BANDGAP, lm335::BANDGAP as i16),
+    (OFFSET, 0),
+    // A GL5528 under a 10 kΩ resistor.
+    (LDR_FIXED, 100),
+    (LDR_R10, 150),
+    (LDR_GAMMA, 70),
+    (LDR_WIRING, 0),
+    (OVERSAMPLE, 2),
+];
+
+// Buttons are looked at every TICK ms, the sensors read every READ_TICKS.
+const TICK: u16 = 20;
+const READ_TICKS: u8 = 50;
+
+const SETTINGS_ADDR: u16 = 0;
+const SETTINGS_SIZE: usize = SAVED.len() * 2;
+
+fn set_reference(reference: Reference) {
+    let adc = unsafe { &*avr_device::atmega328p::ADC::ptr() };
+    adc.admux.modify(|_, w| match reference {
+        Reference::AVcc => w.refs().avcc(),
+        Reference::Internal => w.refs().internal(),
+    });
+    // The reference pin capacitor has to charge or drain first.
+    arduino_hal::delay_ms(5);
+}
+
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = arduino_hal::Peripherals::take().unwrap();
@@ -26,10 +143,12 @@ fn main() -> ! {
 
     let light = pins.a1.into_analog_input(&mut adc);
 
-    let button1 = pins.d7.into_pull_up_input(); // SW1: Down
-    let button2 = pins.d6.into_pull_up_input(); // SW2: Select
-    let button3 = pins.d5.into_pull_up_input(); // SW3: Back
-    let button4 = pins.d4.into_pull_up_input(); // SW4: Up
+    let buttons = [
+        (pins.d7.into_pull_up_input().downgrade(), Key::Down), // SW1
+        (pins.d6.into_pull_up_input().downgrade(), Key::Select), // SW2
+        (pins.d5.into_pull_up_input().downgrade(), Key::Back), // SW3
+        (pins.d4.into_pull_up_input().downgrade(), Key::Up), // SW4
+    ];
 
     let sda = pins.a4.into_pull_up_input();
     let scl = pins.a5.into_pull_up_input();
@@ -42,125 +161,231 @@ fn main() -> ! {
         .with_cursor(Cursor::Off)
         .build();
 
-    let menu_items = ["1. Temperature", "2. LM335", "3. Light"];
-    let mut selected_index = 0;
-    let items_per_screen = 2;
-    let mut current_page = 0;
+    let mut eep = Eeprom::new(dp.EEPROM);
 
-    update_menu_display(&mut lcd, &menu_items, selected_index, current_page, items_per_screen);
+    let mut menu = Menu::new(MENU);
+    menu.set_value(LOG, 1);
+    let mut settings = [0u8; SETTINGS_SIZE];
+    let loaded = persist::load(&mut eep, SETTINGS_ADDR, &mut settings);
+    for (&(id, default), bytes) in SAVED.iter().zip(settings.chunks(2)) {
+        menu.set_value(id, if loaded { i16::from_le_bytes([bytes[0], bytes[1]]) } else { default });
+    }
+    menu.render(&mut lcd);
 
-    let mut menu_open = true;
-    let mut menu_title_rendered = false;
+    let mut held = [false; 4];
+    let mut ticks = 0;
 
-    let mut temp: u8 = 0;
+    let mut readings = Readings::new();
 
     loop {
-        let mut button_pressed = false;
-
-        if button1.is_low() {
-            ufmt::uwriteln!(&mut serial, "Down pressed").unwrap();
-            if selected_index < menu_items.len() - 1 {
-                selected_index += 1;
-                if selected_index >= (current_page + 1) * items_per_screen {
-                    current_page += 1;
+        for (i, (button, key)) in buttons.iter().enumerate() {
+            let pressed = button.is_low();
+            if pressed && !held[i] {
+                match menu.press(*key) {
+                    Some(Event::Opened(_)) => {
+                        lcd.clear();
+                        lcd.set_position(0, 0);
+                        lcd.print(menu.selected().label());
+                        // Show the value right away.
+                        ticks = 0;
+                    }
+                    Some(Event::Changed(id)) if SAVED.iter().any(|&(saved, _)| saved == id) => {
+                        let mut settings = [0u8; SETTINGS_SIZE];
+                        for (&(id, _), bytes) in SAVED.iter().zip(settings.chunks_mut(2)) {
+                            bytes.copy_from_slice(&menu.value(id).to_le_bytes());
+                        }
+                        persist::store(&mut eep, SETTINGS_ADDR, &settings);
+                        menu.render(&mut lcd);
+                    }
+                    Some(_) => menu.render(&mut lcd),
+                    None => {}
                 }
-                button_pressed = true;
             }
-        } else if button4.is_low() {
-            ufmt::uwriteln!(&mut serial, "Up pressed").unwrap();
-            if selected_index > 0 {
-                selected_index -= 1;
-                if selected_index < current_page * items_per_screen {
-                    current_page -= 1;
-                }
-                button_pressed = true;
-            }
-        } else if button2.is_low() {
-            ufmt::uwriteln!(&mut serial, "Selected: {}", selected_index).unwrap();
-            button_pressed = true;
-            menu_open = false;
-        } else if button3.is_low() {
-            ufmt::uwriteln!(&mut serial, "Back to menu").unwrap();
-            button_pressed = true;
-            menu_open = true;
-            menu_title_rendered = false;
-            selected_index = 0;
+            held[i] = pressed;
         }
 
-        if button_pressed && menu_open {
-            update_menu_display(&mut lcd, &menu_items, selected_index, current_page, items_per_screen);
-            arduino_hal::delay_ms(200);
-        }
+        if ticks == 0 {
+            ticks = READ_TICKS;
+
+            let log = menu.value(LOG) != 0;
+            let fahrenheit = menu.value(UNITS) == 1;
 
-        if !menu_open {
-            if !menu_title_rendered {
-                lcd.clear();
-                lcd.set_position(0, 0);
-                lcd.print(menu_items[selected_index]);
-                menu_title_rendered = true;
+            readings.tick();
+            match dht11.read() {
+                Ok(sensor_reading) => {
+                    readings.good(sensor_reading.temperature as i32 * 10, sensor_reading.humidity);
+                }
+                Err(SensorError::ChecksumMismatch) => readings.failed(Failure::Checksum),
+                Err(_) => readings.failed(Failure::Timeout),
             }
 
-            lcd.set_position(0, 1);
-            if selected_index == 0 {
-                let temp_str = convert_to_string::<2>(temp);
-                lcd.print(temp_str.as_str());
-                ufmt::uwriteln!(&mut serial, "temp {}", temp).unwrap();
-            } else if selected_index == 1 {
-                let lm335_value = lm335.analog_read(&mut adc);
+            match menu.open() {
+                Some(id @ (TEMPERATURE | HUMIDITY | DEW_POINT | HEAT_INDEX)) => {
+                    let mut text = match readings.last() {
+                        Some((celsius, humidity)) => match id {
+                            HUMIDITY => {
+                                let mut text = convert_to_string::<16>(humidity);
+                                let _ = text.push_str(" %");
+                                text
+                            }
+                            DEW_POINT => temperature_text(climate::dew_point(celsius, humidity), fahrenheit),
+                            HEAT_INDEX => temperature_text(climate::heat_index(celsius, humidity), fahrenheit),
+                            _ => temperature_text(celsius, fahrenheit),
+                        },
+                        None => {
+                            let mut text = heapless::String::new();
+                            let _ = text.push_str("No reading yet");
+                            text
+                        }
+                    };
+                    if readings.is_stale() {
+                        let _ = ufmt::uwrite!(&mut text, " {}s old", readings.age());
+                    }
+                    print_line(&mut lcd, 1, text.as_str());
+                    if log {
+                        ufmt::uwriteln!(&mut serial, "dht {}", text.as_str()).unwrap();
+                    }
+                }
+                Some(DHT_ERRORS) => {
+                    let checksum = readings.errors(Failure::Checksum);
+                    let timeouts = readings.errors(Failure::Timeout);
+                    let mut text: heapless::String<16> = heapless::String::new();
+                    let _ = ufmt::uwrite!(&mut text, "chk {} tmo {}", checksum, timeouts);
+                    print_line(&mut lcd, 1, text.as_str());
+                    if log {
+                        ufmt::uwriteln!(&mut serial, "dht checksum {} timeout {}", checksum, timeouts).unwrap();
+                    }
+                }
+                Some(LM335) => {
+                    let bandgap = menu.value(BANDGAP) as u32;
+                    let (reference, reference_mv) = if menu.value(REFERENCE) == 1 {
+                        (Reference::Internal, bandgap)
+                    } else {
+                        // The first reading after switching to the bandgap
+                        // channel is off.
+                        adc.read_blocking(&channel::Vbg);
+                        (Reference::AVcc, lm335::vcc(adc.read_blocking(&channel::Vbg), bandgap))
+                    };
 
-                let temp_str = convert_to_string::<3>(lm335_value);
-                lcd.print(temp_str.as_str());
-                ufmt::uwriteln!(&mut serial, "temp {}", lm335_value).unwrap();
-            } else if selected_index == 2 {
-                let light_value = light.analog_read(&mut adc);
+                    if reference == Reference::Internal {
+                        set_reference(reference);
+                        lm335.analog_read(&mut adc);
+                    }
+                    let lm335_value = lm335.analog_read(&mut adc);
+                    if reference == Reference::Internal {
+                        // The light sensor needs the full range.
+                        set_reference(Reference::AVcc);
+                    }
 
-                let temp_str = convert_to_string::<3>(light_value);
-                lcd.print(temp_str.as_str());
+                    // Full scale at the sensor, the divider is the same
+                    // whichever reference is used.
+                    let sensor_mv = (reference_mv * menu.value(DIVIDER) as u32 + 50) / 100;
+                    let celsius = lm335::celsius(lm335_value, sensor_mv, menu.value(OFFSET));
+                    let text = temperature_text(celsius, fahrenheit);
+                    print_line(&mut lcd, 1, text.as_str());
+                    if log {
+                        ufmt::uwriteln!(
+                            &mut serial,
+                            "lm335 {} ref {} mV temp {}",
+                            lm335_value,
+                            reference_mv,
+                            text.as_str()
+                        )
+                        .unwrap();
+                    }
+                }
+                Some(LIGHT) => {
+                    let extra_bits = menu.value(OVERSAMPLE) as u8;
+                    let bandgap = menu.value(BANDGAP) as u32;
+                    adc.read_blocking(&channel::Vbg);
+                    let vcc = lm335::vcc(adc.read_blocking(&channel::Vbg), bandgap);
 
-                ufmt::uwriteln!(&mut serial, "light {}", light_value).unwrap();
-            }
-        }
+                    let mut reference = vcc;
+                    let mut reading = oversample(|| light.analog_read(&mut adc), extra_bits);
+                    // Under the bandgap voltage the internal reference
+                    // resolves about four times finer, with some margin for
+                    // the bandgap being off.
+                    if reading * vcc < (1024 << extra_bits) * (bandgap * 9 / 10) {
+                        set_reference(Reference::Internal);
+                        light.analog_read(&mut adc);
+                        reading = oversample(|| light.analog_read(&mut adc), extra_bits);
+                        reference = bandgap;
+                        set_reference(Reference::AVcc);
+                    }
 
-        match dht11.read() {
-                Ok(sensor_reading) => {
-                    // let hum = sensor_reading.humidity;
-                    temp = sensor_reading.temperature;
+                    let sensor = Ldr {
+                        fixed: menu.value(LDR_FIXED) as u32 * 100,
+                        r10: menu.value(LDR_R10) as u32 * 100,
+                        gamma: menu.value(LDR_GAMMA) as u16,
+                        wiring: if menu.value(LDR_WIRING) == 1 { Wiring::LdrToVcc } else { Wiring::LdrToGround },
+                    };
+                    let ratio = ldr::ratio(reading, 10 + extra_bits, reference, vcc);
+
+                    let mut text: heapless::String<16> = heapless::String::new();
+                    match sensor.resistance(ratio) {
+                        Some(resistance) => {
+                            let lux = sensor.lux(resistance);
+                            // Tenths only mean something in the dark.
+                            if lux < 10000 {
+                                let mut buf = [0; fixed::MAX_LEN];
+                                let _ = text.push_str(fixed::format(lux as i32, 1, &mut buf));
+                            } else {
+                                let _ = ufmt::uwrite!(&mut text, "{}", lux / 10);
+                            }
+                            let _ = text.push_str(" lx");
+                            if log {
+                                ufmt::uwriteln!(
+                                    &mut serial,
+                                    "light {} ref {} mV ldr {} ohm {}",
+                                    reading,
+                                    reference,
+                                    resistance,
+                                    text.as_str()
+                                )
+                                .unwrap();
+                            }
+                        }
+                        None => {
+                            let _ = text.push_str("Out of range");
+                        }
+                    }
+                    print_line(&mut lcd, 1, text.as_str());
                 }
-                Err(_error) => {}
+                _ => {}
+            }
         }
+        ticks -= 1;
 
-
-        arduino_hal::delay_ms(1000);
+        arduino_hal::delay_ms(TICK);
     }
 }
 
-fn update_menu_display<T, D>(
-    lcd: &mut LcdDisplay<T, D>,
-    menu_items: &[&str],
-    selected_index: usize,
-    current_page: usize,
-    items_per_screen: usize,
-) where
+/// Replaces a whole row, so a shorter value does not leave digits of the
+/// previous one behind.
+fn print_line<T, D>(lcd: &mut LcdDisplay<T, D>, row: u8, text: &str)
+where
     T: OutputPin + Sized,
     D: DelayNs + Sized,
 {
-    lcd.clear();
-
-    let start_index = current_page * items_per_screen;
-
-    for i in 0..items_per_screen {
-        let menu_index = start_index + i;
-        if menu_index < menu_items.len() {
-            lcd.set_position(0, i as u8);
-            if menu_index == selected_index {
-                lcd.print("> ");
-            } else {
-                lcd.print("  ");
-            }
+    lcd.set_position(0, row);
+    lcd.print(text);
+    for _ in text.len()..COLUMNS as usize {
+        lcd.print(" ");
+    }
+}
 
-            lcd.print(menu_items[menu_index]);
-        }
+/// Tenths of °C as "21.5 C", or in °F.
+fn temperature_text(celsius: i32, fahrenheit: bool) -> heapless::String<16> {
+    let mut buf = [0; fixed::MAX_LEN];
+    let mut text = heapless::String::new();
+    if fahrenheit {
+        let _ = text.push_str(fixed::format(lm335::fahrenheit(celsius), 1, &mut buf));
+        let _ = text.push_str(" F");
+    } else {
+        let _ = text.push_str(fixed::format(celsius, 1, &mut buf));
+        let _ = text.push_str(" C");
     }
+    text
 }
 
 fn convert_to_string<const N: usize>(value: impl ufmt::uDisplay) -> heapless::String<N> {
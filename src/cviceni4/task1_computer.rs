@@ -0,0 +1,394 @@
+use std::error::Error;
+
+// Only oversampling of the analog helpers is used here.
+#[allow(dead_code)]
+#[path = "../common"]
+mod common {
+    pub mod analog;
+    pub mod fixed;
+    pub mod menu;
+    pub mod report;
+}
+#[allow(dead_code)]
+mod climate;
+mod ldr;
+// The reference selection only matters on the board.
+#[allow(dead_code)]
+mod lm335;
+
+use common::fixed;
+use common::menu::{Event, Item, Key, Menu, Screen, COLUMNS, DEPTH, ROWS};
+use common::report::Report;
+
+const USAGE: &str = "Usage:
+  task1_computer menu
+  task1_computer lm335
+  task1_computer climate
+  task1_computer light";
+
+/// Keeps what a 16x2 LCD would show.
+struct FakeLcd {
+    cells: [[u8; COLUMNS as usize]; ROWS as usize],
+    col: usize,
+    row: usize,
+}
+
+impl FakeLcd {
+    fn new() -> Self {
+        FakeLcd { cells: [[b'#'; COLUMNS as usize]; ROWS as usize], col: 0, row: 0 }
+    }
+
+    fn lines(&self) -> [String; ROWS as usize] {
+        self.cells.map(|row| String::from_utf8_lossy(&row).into_owned())
+    }
+}
+
+impl Screen for FakeLcd {
+    fn clear(&mut self) {
+        self.cells = [[b' '; COLUMNS as usize]; ROWS as usize];
+        self.col = 0;
+        self.row = 0;
+    }
+
+    fn set_position(&mut self, col: u8, row: u8) {
+        self.col = col as usize;
+        self.row = row as usize;
+    }
+
+    fn print(&mut self, text: &str) {
+        for byte in text.bytes() {
+            // The controller keeps what runs off the edge out of sight.
+            if self.col < COLUMNS as usize {
+                self.cells[self.row][self.col] = byte;
+            }
+            self.col += 1;
+        }
+    }
+}
+
+const TEMPERATURE: u8 = 0;
+const LIGHT: u8 = 1;
+const OFFSET: u8 = 0;
+const UNITS: u8 = 1;
+const LOG: u8 = 2;
+
+const MENU: &[Item] = &[
+    Item::Action { label: "Temperature", id: TEMPERATURE },
+    Item::Action { label: "Light", id: LIGHT },
+    Item::Submenu {
+        label: "Settings",
+        items: &[
+            Item::Number { label: "Offset", id: OFFSET, min: -50, max: 50, step: 5, decimals: 1 },
+            Item::Choice { label: "Units", id: UNITS, options: &["C", "F", "K"] },
+            Item::Toggle { label: "Serial log", id: LOG },
+            Item::Submenu {
+                label: "Deeper",
+                items: &[Item::Submenu { label: "Deepest", items: &[Item::Submenu { label: "Too deep", items: &[Item::Action { label: "Unreachable", id: LIGHT }] }] }],
+            },
+        ],
+    },
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("menu") if args.len() == 2 => {
+            if !check_menu() {
+                std::process::exit(1);
+            }
+        }
+        Some("lm335") if args.len() == 2 => {
+            if !check_lm335() {
+                std::process::exit(1);
+            }
+        }
+        Some("climate") if args.len() == 2 => {
+            if !check_climate() {
+                std::process::exit(1);
+            }
+        }
+        Some("light") if args.len() == 2 => {
+            if !check_light() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Walks the menu with scripted button presses and compares the display
+/// with what it should show, returns whether all of it matched.
+fn check_menu() -> bool {
+    let mut report = Report::new();
+
+    let mut menu = Menu::new(MENU);
+    let mut lcd = FakeLcd::new();
+    report.check("first items", shows(&menu, &mut lcd, [">Temperature", " Light"]));
+    report.check("up at the top does nothing", menu.press(Key::Up).is_none());
+    report.check("down moves the cursor", menu.press(Key::Down) == Some(Event::Moved) && shows(&menu, &mut lcd, [" Temperature", ">Light"]));
+    menu.press(Key::Down);
+    report.check("scrolls to keep the cursor", shows(&menu, &mut lcd, [" Light", ">Settings"]));
+    report.check("down at the bottom does nothing", menu.press(Key::Down).is_none());
+    menu.press(Key::Up);
+    report.check("up keeps the scroll", shows(&menu, &mut lcd, [">Light", " Settings"]));
+    report.check("back at the top does nothing", menu.press(Key::Back).is_none());
+
+    let opened = menu.press(Key::Select) == Some(Event::Opened(LIGHT)) && menu.open() == Some(LIGHT);
+    lcd.clear();
+    lcd.print("123 lux");
+    let left_alone = shows(&menu, &mut lcd, ["123 lux", ""]) && menu.press(Key::Down).is_none();
+    let closed = menu.press(Key::Back) == Some(Event::Closed(LIGHT)) && menu.open().is_none();
+    report.check("action opens and closes", opened && closed);
+    report.check("open action keeps the display", left_alone);
+    report.check("back from an action keeps the cursor", shows(&menu, &mut lcd, [">Light", " Settings"]));
+
+    menu.press(Key::Down);
+    menu.press(Key::Select);
+    report.check("submenu starts at its top", shows(&menu, &mut lcd, [">Offset      0.0", " Units         C"]));
+    menu.press(Key::Down);
+    menu.press(Key::Down);
+    report.check("submenu scrolls", shows(&menu, &mut lcd, [" Units         C", ">Serial log  off"]));
+    menu.press(Key::Back);
+    report.check("back lands on the submenu", shows(&menu, &mut lcd, [" Light", ">Settings"]));
+    menu.press(Key::Select);
+    report.check("submenu remembers its cursor", shows(&menu, &mut lcd, [" Units         C", ">Serial log  off"]));
+
+    let toggled = menu.press(Key::Select) == Some(Event::Changed(LOG)) && menu.value(LOG) == 1;
+    report.check("toggle flips on select", toggled && shows(&menu, &mut lcd, [" Units         C", ">Serial log   on"]));
+    menu.press(Key::Select);
+    report.check("toggle flips back", menu.value(LOG) == 0);
+
+    menu.press(Key::Up);
+    menu.press(Key::Select);
+    report.check("choice editor", shows(&menu, &mut lcd, ["Units", "< C >"]));
+    menu.press(Key::Down);
+    report.check("choice wraps down", shows(&menu, &mut lcd, ["Units", "< K >"]));
+    menu.press(Key::Up);
+    menu.press(Key::Up);
+    report.check("choice wraps up", shows(&menu, &mut lcd, ["Units", "< F >"]) && menu.value(UNITS) == 0);
+    let changed = menu.press(Key::Select) == Some(Event::Changed(UNITS)) && menu.value(UNITS) == 1;
+    report.check("select keeps the choice", changed && shows(&menu, &mut lcd, [">Units         F", " Serial log  off"]));
+
+    menu.press(Key::Up);
+    menu.set_value(OFFSET, -10);
+    menu.press(Key::Select);
+    report.check("number editor", shows(&menu, &mut lcd, ["Offset", "< -1.0 >"]));
+    for _ in 0..30 {
+        menu.press(Key::Up);
+    }
+    report.check("number stops at the maximum", shows(&menu, &mut lcd, ["Offset", "< 5.0 >"]));
+    let cancelled = menu.press(Key::Back) == Some(Event::Moved) && menu.value(OFFSET) == -10;
+    report.check("back throws the edit away", cancelled && shows(&menu, &mut lcd, [">Offset     -1.0", " Units         F"]));
+    menu.press(Key::Select);
+    for _ in 0..3 {
+        menu.press(Key::Down);
+    }
+    let changed = menu.press(Key::Select) == Some(Event::Changed(OFFSET)) && menu.value(OFFSET) == -25;
+    report.check("select keeps the number", changed && shows(&menu, &mut lcd, [">Offset     -2.5", " Units         F"]));
+    for _ in 0..30 {
+        menu.press(Key::Select);
+        menu.press(Key::Down);
+        menu.press(Key::Select);
+    }
+    report.check("number stops at the minimum", menu.value(OFFSET) == -50);
+
+    for _ in 0..3 {
+        menu.press(Key::Down);
+    }
+    let mut depth = 2;
+    while menu.press(Key::Select) == Some(Event::Moved) {
+        depth += 1;
+    }
+    report.check("submenus stop at the depth limit", depth == DEPTH && shows(&menu, &mut lcd, [">Too deep", ""]));
+    for _ in 1..DEPTH {
+        menu.press(Key::Back);
+    }
+    report.check("back all the way up", shows(&menu, &mut lcd, [" Light", ">Settings"]));
+
+    let mut buf = [0; fixed::MAX_LEN];
+    let formats = [(215, 1, "21.5"), (-5, 1, "-0.5"), (0, 2, "0.00"), (7, 0, "7"), (-1234, 0, "-1234"), (i32::MIN, 9, "-2.147483648")]
+        .iter()
+        .all(|&(value, decimals, text)| fixed::format(value, decimals, &mut buf) == text);
+    report.check("fixed-point formatting", formats);
+
+    report.passed()
+}
+
+fn shows(menu: &Menu, lcd: &mut FakeLcd, lines: [&str; ROWS as usize]) -> bool {
+    menu.render(lcd);
+    lcd.lines() == lines.map(|line| format!("{:<16}", line))
+}
+
+/// Compares the integer LM335 conversions with floating point, returns
+/// whether they agree.
+fn check_lm335() -> bool {
+    let mut report = Report::new();
+
+    // Off by a tenth only where the exact value is close to a half.
+    for reference in [5000, 4650, 3300] {
+        let mut worst = 0.0f64;
+        for reading in 0..1024u16 {
+            let exact = (reading as f64 * reference as f64 / 1024.0 / 10.0 - 273.15) * 10.0;
+            worst = worst.max((lm335::celsius(reading, reference, 0) as f64 - exact).abs());
+        }
+        report.check(&format!("celsius at {} mV reference", reference), worst <= 0.55);
+    }
+    report.check("offset adds tenths", lm335::celsius(610, 5000, -15) == lm335::celsius(610, 5000, 0) - 15);
+
+    let fahrenheit = (-500..1500).all(|c| {
+        let exact = c as f64 * 9.0 / 5.0 + 320.0;
+        (lm335::fahrenheit(c) as f64 - exact).abs() <= 0.5
+    });
+    report.check("fahrenheit", fahrenheit && lm335::fahrenheit(0) == 320 && lm335::fahrenheit(-400) == -400);
+
+    let vcc = [1000, lm335::BANDGAP, 1200].iter().all(|&bandgap| {
+        (4000..5500u32).all(|vcc| {
+            let reading = (bandgap as f64 * 1024.0 / vcc as f64).round() as u16;
+            // One count of the bandgap reading is worth about 20 mV here.
+            let count = vcc as f64 * vcc as f64 / (bandgap as f64 * 1024.0);
+            (lm335::vcc(reading, bandgap) as f64 - vcc as f64).abs() <= count
+        })
+    });
+    report.check("vcc from the bandgap", vcc);
+
+    let rounding = [(15, 10, 2), (-15, 10, -2), (14, 10, 1), (-14, 10, -1), (0, 7, 0)]
+        .iter()
+        .all(|&(value, divisor, expected)| fixed::divide_rounded(value, divisor) == expected);
+    report.check("rounded division", rounding);
+
+    report.passed()
+}
+
+/// Compares dew point and heat index with floating point versions of the
+/// same formulas, returns whether they agree.
+fn check_climate() -> bool {
+    let mut report = Report::new();
+
+    // The DHT11 range, 0 to 50 °C and 20 to 90 %, and some more.
+    let mut worst = 0.0f64;
+    for celsius in (-200..=600).step_by(5) {
+        for humidity in 5..=100u8 {
+            let t = celsius as f64 / 10.0;
+            let gamma = (humidity as f64 / 100.0).ln() + 17.62 * t / (243.12 + t);
+            let exact = 243.12 * gamma / (17.62 - gamma) * 10.0;
+            worst = worst.max((climate::dew_point(celsius, humidity) as f64 - exact).abs());
+        }
+    }
+    report.value("dew point worst error (tenths)", worst, worst <= 1.0);
+    report.check("dew point at saturation", climate::dew_point(215, 100) == 215);
+
+    let mut worst = 0.0f64;
+    for celsius in (0..=500).step_by(5) {
+        for humidity in 5..=100u8 {
+            let exact = (heat_index(celsius as f64 / 10.0 * 1.8 + 32.0, humidity as f64) - 32.0) / 1.8 * 10.0;
+            worst = worst.max((climate::heat_index(celsius, humidity) as f64 - exact).abs());
+        }
+    }
+    // Rounding on the way to °F and back adds up to a bit over a tenth.
+    report.value("heat index worst error (tenths)", worst, worst <= 1.5);
+    let mild = (climate::heat_index(200, 50) - 200).abs() <= 10;
+    report.value("heat index of mild air", climate::heat_index(200, 50) as f64 / 10.0, mild);
+    // The NOAA table gives 121 °F for 96 °F at 65 %.
+    let hot = climate::heat_index(356, 65);
+    report.value("heat index of hot humid air", hot as f64 / 10.0, (hot - 494).abs() <= 5);
+
+    let mut readings = climate::Readings::new();
+    let empty = readings.last().is_none() && !readings.is_stale();
+    readings.good(215, 40);
+    for _ in 0..climate::STALE_AFTER - 1 {
+        readings.tick();
+        readings.failed(climate::Failure::Timeout);
+    }
+    let fresh = !readings.is_stale() && readings.age() == climate::STALE_AFTER - 1;
+    readings.tick();
+    readings.failed(climate::Failure::Checksum);
+    let stale = readings.is_stale() && readings.last() == Some((215, 40));
+    let counted = readings.errors(climate::Failure::Timeout) == climate::STALE_AFTER - 1
+        && readings.errors(climate::Failure::Checksum) == 1;
+    readings.good(220, 41);
+    let renewed = !readings.is_stale() && readings.age() == 0;
+    report.check("staleness and error counts", empty && fresh && stale && counted && renewed);
+
+    report.passed()
+}
+
+/// The NOAA heat index in °F.
+fn heat_index(t: f64, rh: f64) -> f64 {
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    if (simple + t) / 2.0 < 80.0 {
+        return simple;
+    }
+    let mut hi = -42.379 + 2.04901523 * t + 10.14333127 * rh - 0.22475541 * t * rh - 0.00683783 * t * t
+        - 0.05481717 * rh * rh
+        + 0.00122874 * t * t * rh
+        + 0.00085282 * t * rh * rh
+        - 0.00000199 * t * t * rh * rh;
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        hi -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+    } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        hi += (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0);
+    }
+    hi
+}
+
+/// Compares the light sensor model and the fixed-point math under it with
+/// floating point, returns whether they agree.
+fn check_light() -> bool {
+    let mut report = Report::new();
+
+    let worst = (1..100_000u32)
+        .map(|value| (fixed::log2(value) as f64 / 65536.0 - (value as f64).log2()).abs())
+        .fold(0.0, f64::max);
+    report.value("log2 worst error", worst, worst < 0.0001);
+    // Relative error past what rounding to a whole number costs.
+    let worst = (-65536..31 * 65536)
+        .step_by(997)
+        .map(|value| {
+            let exact = 2f64.powf(value as f64 / 65536.0);
+            ((fixed::exp2(value) as f64 - exact).abs() - 0.5).max(0.0) / exact
+        })
+        .fold(0.0, f64::max);
+    report.value("exp2 worst relative error", worst, worst < 0.0001);
+    report.check("exp2 saturates", fixed::exp2(40 << 16) == u32::MAX && fixed::exp2(-5 << 16) == 0);
+
+    // Noise of a count around 511.25 averages out to the fraction.
+    let mut noise = [511u16, 511, 512, 511].iter().copied().cycle();
+    let oversampled = common::analog::oversample(|| noise.next().unwrap(), 2);
+    report.value("oversampling to 12 bits", oversampled as f64, oversampled == 2045);
+
+    let ratio = ldr::ratio(2048, 12, 5000, 5000);
+    report.value("ratio against AVcc", ratio as f64 / ldr::FULL_SCALE as f64, ratio == ldr::FULL_SCALE / 2);
+    let ratio = ldr::ratio(1023, 10, 1100, 4400);
+    let exact = 1023.0 / 1024.0 * 1100.0 / 4400.0;
+    report.value("ratio against the bandgap", ratio as f64 / ldr::FULL_SCALE as f64, (ratio as f64 / ldr::FULL_SCALE as f64 - exact).abs() < 1e-6);
+
+    let mut sensor = ldr::Ldr { fixed: 10_000, r10: 15_000, gamma: 70, wiring: ldr::Wiring::LdrToGround };
+    let to_ground = sensor.resistance(ldr::FULL_SCALE / 4) == Some(3333);
+    sensor.wiring = ldr::Wiring::LdrToVcc;
+    let to_vcc = sensor.resistance(ldr::FULL_SCALE / 4) == Some(30_000);
+    let ends = sensor.resistance(0).is_none() && sensor.resistance(ldr::FULL_SCALE).is_none();
+    report.check("divider resistance", to_ground && to_vcc && ends);
+
+    let mut worst = 0.0f64;
+    for gamma in [50, 70, 100] {
+        sensor.gamma = gamma;
+        for resistance in (100..2_000_000).step_by(1013) {
+            let exact = 100.0 * (15_000.0 / resistance as f64).powf(100.0 / gamma as f64);
+            if !(10.0..=1e9).contains(&exact) {
+                continue;
+            }
+            worst = worst.max(((sensor.lux(resistance) as f64 - exact).abs() - 0.5).max(0.0) / exact);
+        }
+    }
+    report.value("lux worst relative error", worst, worst < 0.002);
+    sensor.gamma = 70;
+    report.value("10 lux at R10", sensor.lux(15_000) as f64 / 10.0, sensor.lux(15_000) == 100);
+
+    report.passed()
+}
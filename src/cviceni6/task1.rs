@@ -2,24 +2,46 @@
 #![no_main]
 #![feature(abi_avr_interrupt)]
 
+use arduino_hal::hal::port::{PB3, PB4, PB5};
+use arduino_hal::port::mode::Output;
+use arduino_hal::port::Pin;
+use avr_device::interrupt::Mutex;
 use panic_halt as _;
 
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::color::Rgb;
+use common::rgb_led::{Digital, RgbLed};
+
+use core::cell::RefCell;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
+type Led = RgbLed<Digital<Pin<Output, PB5>>, Digital<Pin<Output, PB4>>, Digital<Pin<Output, PB3>>>;
+
+static LED: Mutex<RefCell<Option<Led>>> = Mutex::new(RefCell::new(None));
 static LED_IS_OFF: AtomicBool = AtomicBool::new(false);
 static LED_COLOR: AtomicU8 = AtomicU8::new(0);
 static PIN_CHANGED: AtomicBool = AtomicBool::new(false);
 
 // Mapping: 0 => red (D13 -> PB5), 1 => green (D12 -> PB4), 2 => blue (D11 -> PB3)
-fn color_to_state(color: u8) -> u8 {
+fn color_to_rgb(color: u8) -> Rgb {
     match color {
-        0 => 1 << 5, // red: set PB5 high
-        1 => 1 << 4, // green: set PB4 high
-        2 => 1 << 3, // blue: set PB3 high
-        _ => 0,
+        0 => Rgb::RED,
+        1 => Rgb::GREEN,
+        2 => Rgb::BLUE,
+        _ => Rgb::OFF,
     }
 }
 
+fn set_led(color: Rgb) {
+    avr_device::interrupt::free(|cs| {
+        if let Some(led) = LED.borrow(cs).borrow_mut().as_mut() {
+            led.set(color);
+        }
+    });
+}
+
 fn debounce_delay() {
     for _ in 0..32000 {
         unsafe { core::ptr::read_volatile(&0u8) };
@@ -41,11 +63,8 @@ fn PCINT2() {
     let next = (current + 1) % 3;
     LED_COLOR.store(next, Ordering::SeqCst);
 
-    let new_state = color_to_state(next);
     if !LED_IS_OFF.load(Ordering::SeqCst) {
-        unsafe {
-            (*avr_device::atmega328p::PORTB::ptr()).portb.write(|w| w.bits(new_state));
-        }
+        set_led(color_to_rgb(next));
     }
 }
 
@@ -56,6 +75,9 @@ fn main() -> ! {
 
     let _joystick_up = pins.d2.into_floating_input().downgrade();
 
+    let led = RgbLed::digital(pins.d13.into_output(), pins.d12.into_output(), pins.d11.into_output());
+    avr_device::interrupt::free(|cs| LED.borrow(cs).replace(Some(led)));
+
     dp.EXINT.pcicr.write(|w| unsafe { w.bits(0b100) });
     dp.EXINT.pcmsk2.write(|w| w.bits(0b100));
 
@@ -63,17 +85,12 @@ fn main() -> ! {
 
     loop {
         let color = LED_COLOR.load(Ordering::SeqCst);
-        let new_state = color_to_state(color);
-        unsafe {
-            (*avr_device::atmega328p::PORTB::ptr()).portb.write(|w| w.bits(new_state));
-        }
+        set_led(color_to_rgb(color));
         LED_IS_OFF.store(false, Ordering::SeqCst);
 
         arduino_hal::delay_ms(1000);
 
-        unsafe {
-            (*avr_device::atmega328p::PORTB::ptr()).portb.write(|w| w.bits(0));
-        }
+        set_led(Rgb::OFF);
 
         LED_IS_OFF.store(true, Ordering::SeqCst);
 
@@ -1,9 +1,19 @@
 #![no_std]
 #![no_main]
+#![feature(abi_avr_interrupt)]
 
 use arduino_hal::prelude::*;
 use panic_halt as _;
 
+#[path = "../common/mod.rs"]
+mod common;
+
+#[path = "../common/millis.rs"]
+mod millis;
+
+use common::joystick::{Calibration, Joystick};
+use common::telemetry::{Encoder, Record};
+
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = arduino_hal::Peripherals::take().unwrap();
@@ -16,14 +26,31 @@ fn main() -> ! {
     let mut green_led = pins.d12.into_output();
     let mut blue_led  = pins.d11.into_output();
 
-    let x_axis = pins.a0.into_analog_input(&mut adc);
-    let y_axis = pins.a1.into_analog_input(&mut adc);
+    let x_axis = pins.a0.into_analog_input(&mut adc).into_channel();
+    let y_axis = pins.a1.into_analog_input(&mut adc).into_channel();
+    let mut joystick = Joystick::new(x_axis, y_axis, Calibration::DEFAULT);
+
+    millis::init(dp.TC0);
+    unsafe { avr_device::interrupt::enable() };
+
+    // `T` switches to binary telemetry frames and back.
+    let mut telemetry: Option<Encoder> = None;
 
     loop {
-        let x_value = x_axis.analog_read(&mut adc);
-        let y_value = y_axis.analog_read(&mut adc);
+        let (reading, direction) = joystick.poll(&mut adc);
 
-        ufmt::uwriteln!(&mut serial, "X: {}, Y: {}", x_value, y_value).unwrap();
+        match telemetry.as_mut() {
+            Some(encoder) => {
+                let record = Record::Joystick { x: reading.x_raw, y: reading.y_raw };
+                encoder.send(millis::millis(), &record, |byte| serial.write_byte(byte));
+            }
+            None => {
+                ufmt::uwriteln!(&mut serial, "X: {}, Y: {}", reading.x_raw, reading.y_raw).unwrap();
+                if let Some(direction) = direction {
+                    ufmt::uwriteln!(&mut serial, "Direction: {}", direction.name()).unwrap();
+                }
+            }
+        }
 
         match serial.read() {
             Ok(byte) => {
@@ -48,6 +75,12 @@ fn main() -> ! {
                         green_led.set_low();
                         blue_led.set_low();
                     }
+                    b'T' | b't' => {
+                        telemetry = match telemetry {
+                            Some(_) => None,
+                            None => Some(Encoder::new()),
+                        };
+                    }
                     _ => {
                         // Nic
                     }
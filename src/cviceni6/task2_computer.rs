@@ -1,11 +1,33 @@
 use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use termion::event::Key;
 use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 
+// Only part of what the firmware uses is exercised here.
+#[allow(dead_code)]
+#[path = "../common"]
+mod common {
+    pub mod crc;
+    pub mod report;
+    pub mod telemetry;
+}
+
+use common::report::Report;
+use common::telemetry::{Decoder, Encoder, Error, Record, Sample, MAX_FRAME};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // `task2_computer telemetry` checks the frame format instead of talking
+    // to the board.
+    if std::env::args().nth(1).as_deref() == Some("telemetry") {
+        if !check_telemetry() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let port = serialport::new("/dev/ttyUSB0", 57600)
         .timeout(Duration::from_millis(10))
         .open()
@@ -14,25 +36,53 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let port = Arc::new(Mutex::new(port));
     let reader_port = Arc::clone(&port);
 
+    let binary = Arc::new(AtomicBool::new(false));
+    let reader_binary = Arc::clone(&binary);
+    // Set when binary mode goes on, the board then counts frames from 0 again.
+    let restart = Arc::new(AtomicBool::new(false));
+    let reader_restart = Arc::clone(&restart);
+
     println!(
-        "Press keys to send commands: 'r' for red, 'g' for green, 'b' for blue. Press 'q' to quit."
+        "Press keys to send commands: 'r' for red, 'g' for green, 'b' for blue, 't' to toggle binary telemetry. Press 'q' to quit."
     );
 
     let stdin = io::stdin();
     let mut stdout = io::stdout().into_raw_mode()?;
 
     std::thread::spawn(move || {
+        let mut decoder = Decoder::new();
         loop {
             let mut serial_buf: Vec<u8> = vec![0; 32];
             let bytes_read = reader_port.lock().unwrap().read(serial_buf.as_mut_slice());
 
             if let Ok(bytes) = bytes_read {
-                let received_text = String::from_utf8_lossy(&serial_buf[..bytes])
-                    .replace("\n", "")
-                    .replace("\r", "");
+                if reader_binary.load(Ordering::Relaxed) {
+                    if reader_restart.swap(false, Ordering::Relaxed) {
+                        decoder.reset();
+                    }
+                    for &byte in &serial_buf[..bytes] {
+                        match decoder.push(byte) {
+                            Some(Ok(sample)) => {
+                                let values = match sample.record {
+                                    Record::Joystick { x, y } => format!("X: {}, Y: {}", x, y),
+                                    Record::Analog { channel, value } => format!("A{}: {}", channel, value),
+                                };
+                                println!("{} ms #{}: {}\r", sample.timestamp, sample.sequence, values);
+                            }
+                            Some(Err(error)) => {
+                                println!("Bad frame: {:?}, {} lost so far\r", error, decoder.lost())
+                            }
+                            None => {}
+                        }
+                    }
+                } else {
+                    let received_text = String::from_utf8_lossy(&serial_buf[..bytes])
+                        .replace("\n", "")
+                        .replace("\r", "");
 
-                if !received_text.is_empty() {
-                    println!("Received: {}\r", received_text);
+                    if !received_text.is_empty() {
+                        println!("Received: {}\r", received_text);
+                    }
                 }
             }
 
@@ -57,6 +107,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("Sent: b (blue)\r\n");
                 stdout.flush()?;
             }
+            Key::Char('t') => {
+                port.lock().unwrap().write_all(b"t")?;
+                let enabled = !binary.load(Ordering::Relaxed);
+                if enabled {
+                    restart.store(true, Ordering::Relaxed);
+                }
+                binary.store(enabled, Ordering::Relaxed);
+                println!("Sent: t (telemetry {})\r\n", if enabled { "binary" } else { "text" });
+                stdout.flush()?;
+            }
             Key::Char('q') => {
                 println!("Quitting...\r\n");
                 stdout.flush()?;
@@ -69,3 +129,61 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Encodes `record` and decodes the frame, returns every result the decoder
+/// gave on the way.
+fn round_trip(encoder: &mut Encoder, decoder: &mut Decoder, timestamp: u32, record: Record) -> Vec<Result<Sample, Error>> {
+    let mut frame = [0u8; MAX_FRAME];
+    let len = encoder.encode(timestamp, &record, &mut frame);
+    frame[..len].iter().filter_map(|&byte| decoder.push(byte)).collect()
+}
+
+/// Sends frames through the encoder and decoder, damaged and lost ones too,
+/// returns whether they all came out as expected.
+fn check_telemetry() -> bool {
+    let mut report = Report::new();
+    let mut encoder = Encoder::new();
+    let mut decoder = Decoder::new();
+
+    // Zero bytes all over: the first sequence number, the timestamp and the
+    // payloads.
+    let records = [
+        (0, Record::Joystick { x: 0, y: 0 }),
+        (0x0100_0000, Record::Joystick { x: 512, y: 1023 }),
+        (0x00FF_00FF, Record::Analog { channel: 0, value: 0 }),
+        (u32::MAX, Record::Analog { channel: 5, value: 256 }),
+    ];
+    let mut all = true;
+    for (sequence, &(timestamp, record)) in records.iter().enumerate() {
+        let decoded = round_trip(&mut encoder, &mut decoder, timestamp, record);
+        all &= decoded == [Ok(Sample { sequence: sequence as u8, timestamp, record })];
+    }
+    report.check("records survive a round trip", all && decoder.lost() == 0);
+
+    let mut frame = [0u8; MAX_FRAME];
+    let len = encoder.encode(1234, &Record::Analog { channel: 1, value: 700 }, &mut frame);
+    // The kind is never zero, so it always directly follows the first COBS
+    // code byte.
+    frame[1] ^= 0x40;
+    let decoded: Vec<_> = frame[..len].iter().filter_map(|&byte| decoder.push(byte)).collect();
+    report.check("a flipped byte fails the checksum", decoded == [Err(Error::Checksum)]);
+
+    let mut decoder = Decoder::new();
+    let mut encoder = Encoder::new();
+    let first = round_trip(&mut encoder, &mut decoder, 10, Record::Joystick { x: 1, y: 2 });
+    let mut dropped = [0u8; MAX_FRAME];
+    encoder.encode(20, &Record::Joystick { x: 3, y: 4 }, &mut dropped);
+    let third = round_trip(&mut encoder, &mut decoder, 30, Record::Joystick { x: 5, y: 6 });
+    report.check("a dropped frame is counted", first.len() == 1 && third.len() == 1 && decoder.lost() == 1);
+    decoder.reset();
+    let restarted = round_trip(&mut Encoder::new(), &mut decoder, 50, Record::Joystick { x: 7, y: 8 });
+    report.check("reset forgets the old sequence", restarted.len() == 1 && decoder.lost() == 0);
+
+    let mut decoder = Decoder::new();
+    let mut decoded: Vec<_> = (0..MAX_FRAME + 5).filter_map(|_| decoder.push(0x55)).collect();
+    decoded.extend(decoder.push(0));
+    let next = round_trip(&mut Encoder::new(), &mut decoder, 40, Record::Analog { channel: 2, value: 3 });
+    report.check("a too long frame overflows", decoded == [Err(Error::Overflow)] && next.len() == 1 && next[0].is_ok());
+
+    report.passed()
+}